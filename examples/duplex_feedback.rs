@@ -0,0 +1,72 @@
+//! Feeds the input of a device back into its output through a single duplex stream.
+//!
+//! Unlike the `feedback` example, input and output are passed to the same callback, so there is
+//! no ring buffer between them and no added delay. Assumes that the device supports the f32 sample
+//! format.
+
+use clap::Parser;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "CPAL duplex feedback example", long_about = None)]
+struct Opt {
+    /// The audio device to use
+    #[arg(short, long, default_value_t = String::from("default"))]
+    device: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opt = Opt::parse();
+    let host = cpal::default_host();
+
+    let device = if opt.device == "default" {
+        host.default_output_device()
+    } else {
+        host.devices()?
+            .find(|x| x.name().map(|y| y == opt.device).unwrap_or(false))
+    }
+    .expect("failed to find device");
+    println!("Using device: \"{}\"", device.name()?);
+
+    let input_config = device.default_input_config()?;
+    let output_config = device.default_output_config()?;
+    let config = cpal::DuplexStreamConfig {
+        input_channels: input_config.channels(),
+        output_channels: output_config.channels(),
+        sample_rate: output_config.sample_rate(),
+        buffer_size: cpal::BufferSize::Default,
+    };
+
+    let input_channels = config.input_channels as usize;
+    let output_channels = config.output_channels as usize;
+    let data_fn = move |input: &[f32], output: &mut [f32], _: &cpal::DuplexCallbackInfo| {
+        // Both buffers hold the same number of frames. Copy each input frame into the output
+        // frame, repeating its last channel if the output has more.
+        for (in_frame, out_frame) in input
+            .chunks(input_channels)
+            .zip(output.chunks_mut(output_channels))
+        {
+            for (ch, sample) in out_frame.iter_mut().enumerate() {
+                *sample = in_frame[ch.min(input_channels - 1)];
+            }
+        }
+    };
+
+    println!("Attempting to build the duplex stream with `{:?}`.", config);
+    let stream = device.build_duplex_stream(&config, data_fn, err_fn, None)?;
+    stream.play()?;
+    if let Some(latency) = stream.latency() {
+        println!("Round trip latency: {:?}", latency);
+    }
+
+    // Run for 3 seconds before closing.
+    println!("Playing for 3 seconds... ");
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    drop(stream);
+    println!("Done!");
+    Ok(())
+}
+
+fn err_fn(err: cpal::StreamError) {
+    eprintln!("an error occurred on stream: {}", err);
+}
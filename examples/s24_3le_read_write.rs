@@ -82,7 +82,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 if let Ok(mut guard) = writer_2.try_lock() {
                     if let Some(writer) = guard.as_mut() {
                         for chunk in data.chunks(input_channels as usize) {
-                            if chunk.len() > 0 {
+                            if !chunk.is_empty() {
                                 let sample = i32::from_sample(chunk[0]);
                                 writer.write_sample(sample).ok();
                             }
@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::time::Duration;
 
 /// The requested host, although supported on this platform, is unavailable.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -196,6 +197,33 @@ impl From<BackendSpecificError> for BuildStreamError {
     }
 }
 
+/// Error that can happen when calling
+/// [`Host::watch_devices()`](crate::traits::HostTrait::watch_devices).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WatchDevicesError {
+    /// The host can't notify of device changes.
+    NotSupported,
+    /// See the [`BackendSpecificError`] docs for more information about this error variant.
+    BackendSpecific { err: BackendSpecificError },
+}
+
+impl Display for WatchDevicesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BackendSpecific { err } => err.fmt(f),
+            Self::NotSupported => f.write_str("The host does not support watching its devices."),
+        }
+    }
+}
+
+impl Error for WatchDevicesError {}
+
+impl From<BackendSpecificError> for WatchDevicesError {
+    fn from(err: BackendSpecificError) -> Self {
+        Self::BackendSpecific { err }
+    }
+}
+
 /// Errors that might occur when calling [`Stream::play()`](crate::traits::StreamTrait::play).
 ///
 /// As of writing this, only macOS may immediately return an error while calling this method. This
@@ -266,6 +294,18 @@ pub enum StreamError {
     /// The device no longer exists. This can happen if the device is disconnected while the
     /// program is running.
     DeviceNotAvailable,
+    /// The device ran out of samples to play because the stream did not provide them in time,
+    /// which is usually audible as a glitch. The stream recovers on its own.
+    ///
+    /// `position` is the number of frames the stream had processed when this happened, and
+    /// `lost` is roughly how long the device went without samples.
+    BufferUnderrun { position: u64, lost: Duration },
+    /// Samples captured by the device were lost because the stream did not read them in time.
+    /// The stream recovers on its own.
+    ///
+    /// `position` is the number of frames the stream had processed when this happened, and
+    /// `lost` is roughly how long the device captured nothing.
+    BufferOverrun { position: u64, lost: Duration },
     /// See the [`BackendSpecificError`] docs for more information about this error variant.
     BackendSpecific { err: BackendSpecificError },
 }
@@ -277,6 +317,16 @@ impl Display for StreamError {
             Self::DeviceNotAvailable => f.write_str(
                 "The requested device is no longer available. For example, it has been unplugged.",
             ),
+            Self::BufferUnderrun { position, lost } => write!(
+                f,
+                "buffer underrun at frame {}, {:?} of audio lost",
+                position, lost
+            ),
+            Self::BufferOverrun { position, lost } => write!(
+                f,
+                "buffer overrun at frame {}, {:?} of audio lost",
+                position, lost
+            ),
         }
     }
 }
@@ -130,7 +130,8 @@ fn default_supported_configs(is_output: bool) -> VecIntoIter<SupportedStreamConf
                     android_format,
                 ) {
                     output.push(SupportedStreamConfigRange {
-                        channels: channel_count as u16,
+                        min_channels: channel_count as u16,
+                        max_channels: channel_count as u16,
                         min_sample_rate: SampleRate(*sample_rate as u32),
                         max_sample_rate: SampleRate(*sample_rate as u32),
                         buffer_size: SupportedBufferSize::Range { min, max },
@@ -190,13 +191,13 @@ fn device_supported_configs(
                     channel_mask,
                     android_format,
                 );
-                output.push(SupportedStreamConfigRange {
-                    channels: cmp::min(*channel_count as u16, 2u16),
-                    min_sample_rate: SampleRate(*sample_rate as u32),
-                    max_sample_rate: SampleRate(*sample_rate as u32),
+                output.push(SupportedStreamConfigRange::new(
+                    cmp::min(*channel_count as u16, 2u16),
+                    SampleRate(*sample_rate as u32),
+                    SampleRate(*sample_rate as u32),
                     buffer_size,
                     sample_format,
-                });
+                ));
             }
         }
     }
@@ -0,0 +1,386 @@
+//! Conversion between the sample layouts used by ALSA devices and cpal's in-memory sample types.
+//!
+//! Many devices only accept formats that have no direct Rust counterpart, e.g. 24-bit samples
+//! packed into 3 bytes (`S24_3LE`) or 20-bit samples stored in a 3-byte container (`S20_3LE`), or
+//! use the opposite byte order to the host. Rather than special-casing each of these in the stream
+//! workers, every linear format is described by a [`Layout`] and converted to and from the layout
+//! of the [`SampleFormat`] exposed to the user by a [`Codec`].
+
+use super::alsa;
+use crate::SampleFormat;
+
+/// The memory layout of a single linear sample.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Layout {
+    /// The number of bytes occupied by one sample.
+    pub width: usize,
+    /// The number of significant bits, stored in the least significant end of the container.
+    pub bits: u32,
+    /// Whether the sample is two's complement signed or offset binary unsigned.
+    pub signed: bool,
+    /// Whether the sample is an IEEE 754 float. Floats are only ever converted to floats of the
+    /// same width, which only requires reordering their bytes.
+    pub float: bool,
+    /// The byte order of the container.
+    pub big_endian: bool,
+}
+
+const LE: bool = false;
+const BE: bool = true;
+
+impl Layout {
+    const fn int(width: usize, bits: u32, signed: bool, big_endian: bool) -> Self {
+        Layout {
+            width,
+            bits,
+            signed,
+            float: false,
+            big_endian,
+        }
+    }
+
+    const fn float(width: usize, big_endian: bool) -> Self {
+        Layout {
+            width,
+            bits: width as u32 * 8,
+            signed: true,
+            float: true,
+            big_endian,
+        }
+    }
+
+    /// The layout of a linear ALSA format, or `None` if `format` is not a linear format.
+    pub fn of_alsa_format(format: alsa::pcm::Format) -> Option<Self> {
+        use alsa::pcm::Format;
+        let layout = match format {
+            Format::S8 => Self::int(1, 8, true, LE),
+            Format::U8 => Self::int(1, 8, false, LE),
+            Format::S16LE => Self::int(2, 16, true, LE),
+            Format::S16BE => Self::int(2, 16, true, BE),
+            Format::U16LE => Self::int(2, 16, false, LE),
+            Format::U16BE => Self::int(2, 16, false, BE),
+            Format::S24LE => Self::int(4, 24, true, LE),
+            Format::S24BE => Self::int(4, 24, true, BE),
+            Format::U24LE => Self::int(4, 24, false, LE),
+            Format::U24BE => Self::int(4, 24, false, BE),
+            Format::S32LE => Self::int(4, 32, true, LE),
+            Format::S32BE => Self::int(4, 32, true, BE),
+            Format::U32LE => Self::int(4, 32, false, LE),
+            Format::U32BE => Self::int(4, 32, false, BE),
+            Format::FloatLE => Self::float(4, LE),
+            Format::FloatBE => Self::float(4, BE),
+            Format::Float64LE => Self::float(8, LE),
+            Format::Float64BE => Self::float(8, BE),
+            Format::S243LE => Self::int(3, 24, true, LE),
+            Format::S243BE => Self::int(3, 24, true, BE),
+            Format::U243LE => Self::int(3, 24, false, LE),
+            Format::U243BE => Self::int(3, 24, false, BE),
+            Format::S203LE => Self::int(3, 20, true, LE),
+            Format::S203BE => Self::int(3, 20, true, BE),
+            Format::U203LE => Self::int(3, 20, false, LE),
+            Format::U203BE => Self::int(3, 20, false, BE),
+            Format::S183LE => Self::int(3, 18, true, LE),
+            Format::S183BE => Self::int(3, 18, true, BE),
+            Format::U183LE => Self::int(3, 18, false, LE),
+            Format::U183BE => Self::int(3, 18, false, BE),
+            _ => return None,
+        };
+        Some(layout)
+    }
+
+    /// The layout cpal uses in memory for `sample_format`, which is always in native byte order.
+    pub fn of_sample_format(sample_format: SampleFormat) -> Option<Self> {
+        const NATIVE: bool = cfg!(target_endian = "big");
+        let layout = match sample_format {
+            SampleFormat::I8 => Self::int(1, 8, true, NATIVE),
+            SampleFormat::I16 => Self::int(2, 16, true, NATIVE),
+            SampleFormat::I24 => Self::int(4, 24, true, NATIVE),
+            SampleFormat::I24Packed => Self::int(3, 24, true, NATIVE),
+            SampleFormat::I32 => Self::int(4, 32, true, NATIVE),
+            SampleFormat::I48 => Self::int(8, 48, true, NATIVE),
+            SampleFormat::I64 => Self::int(8, 64, true, NATIVE),
+            SampleFormat::U8 => Self::int(1, 8, false, NATIVE),
+            SampleFormat::U16 => Self::int(2, 16, false, NATIVE),
+            SampleFormat::U24 => Self::int(4, 24, false, NATIVE),
+            SampleFormat::U32 => Self::int(4, 32, false, NATIVE),
+            SampleFormat::U48 => Self::int(8, 48, false, NATIVE),
+            SampleFormat::U64 => Self::int(8, 64, false, NATIVE),
+            SampleFormat::F32 => Self::float(4, NATIVE),
+            SampleFormat::F64 => Self::float(8, NATIVE),
+            // Companded samples are streamed as they are.
+            SampleFormat::MuLaw | SampleFormat::ALaw => return None,
+        };
+        Some(layout)
+    }
+
+    // Read the sample at the start of `bytes` and return it as a signed value whose most
+    // significant bit is bit 63.
+    #[inline]
+    fn read(&self, bytes: &[u8]) -> i64 {
+        let bytes = &bytes[..self.width];
+        let mut raw = 0u64;
+        if self.big_endian {
+            for &b in bytes {
+                raw = (raw << 8) | b as u64;
+            }
+        } else {
+            for &b in bytes.iter().rev() {
+                raw = (raw << 8) | b as u64;
+            }
+        }
+        if !self.signed {
+            // Offset binary to two's complement.
+            raw ^= 1 << (self.bits - 1);
+        }
+        (raw << (64 - self.bits)) as i64
+    }
+
+    // Write a value produced by `read` to the start of `bytes`, truncating it to `self.bits`.
+    #[inline]
+    fn write(&self, value: i64, bytes: &mut [u8]) {
+        // Arithmetic shift, so signed samples are sign-extended across the whole container.
+        let mut raw = (value >> (64 - self.bits)) as u64;
+        if !self.signed {
+            raw ^= 1 << (self.bits - 1);
+            if self.bits < 64 {
+                raw &= (1 << self.bits) - 1;
+            }
+        }
+        let bytes = &mut bytes[..self.width];
+        if self.big_endian {
+            for b in bytes.iter_mut().rev() {
+                *b = raw as u8;
+                raw >>= 8;
+            }
+        } else {
+            for b in bytes.iter_mut() {
+                *b = raw as u8;
+                raw >>= 8;
+            }
+        }
+    }
+}
+
+/// Converts buffers between the layout of an ALSA device and the layout of the [`SampleFormat`]
+/// delivered to the user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Codec {
+    device: Layout,
+    user: Layout,
+    // Whether this converts between `S24_3LE` and `I24` on a little-endian host, which has a
+    // dedicated fast path.
+    packed_i24: bool,
+}
+
+impl Codec {
+    /// Returns the codec required to stream `sample_format` through a device opened with
+    /// `alsa_format`, or `None` if the two share the same layout and no conversion is needed.
+    pub fn new(alsa_format: alsa::pcm::Format, sample_format: SampleFormat) -> Option<Self> {
+        let device = Layout::of_alsa_format(alsa_format)?;
+        let user = Layout::of_sample_format(sample_format)?;
+        debug_assert!(device.float == user.float && (!user.float || device.width == user.width));
+        // Padding bits are not guaranteed to be sign-extended by the device, so containers with
+        // padding always go through the codec.
+        if device == user && device.bits as usize == device.width * 8 {
+            None
+        } else {
+            let packed_i24 = cfg!(target_endian = "little")
+                && device == Layout::of_alsa_format(alsa::pcm::Format::S243LE)?
+                && user == Layout::of_sample_format(SampleFormat::I24)?;
+            Some(Codec {
+                device,
+                user,
+                packed_i24,
+            })
+        }
+    }
+
+    /// The number of bytes one sample occupies in the device buffer.
+    pub fn device_width(&self) -> usize {
+        self.device.width
+    }
+
+    /// Convert samples read from the device into the user's sample format.
+    pub fn decode(&self, device: &[u8], user: &mut [u8]) {
+        if self.packed_i24 {
+            let (device, user) = unpack_i24(device, user);
+            return self.decode_generic(device, user);
+        }
+        self.decode_generic(device, user);
+    }
+
+    /// Convert samples written by the user into the device's sample format.
+    pub fn encode(&self, user: &[u8], device: &mut [u8]) {
+        if self.packed_i24 {
+            let (user, device) = pack_i24(user, device);
+            return self.encode_generic(user, device);
+        }
+        self.encode_generic(user, device);
+    }
+
+    fn decode_generic(&self, device: &[u8], user: &mut [u8]) {
+        let samples = device
+            .chunks_exact(self.device.width)
+            .zip(user.chunks_exact_mut(self.user.width));
+        for (src, dst) in samples {
+            self.user.write(self.device.read(src), dst);
+        }
+    }
+
+    fn encode_generic(&self, user: &[u8], device: &mut [u8]) {
+        let samples = user
+            .chunks_exact(self.user.width)
+            .zip(device.chunks_exact_mut(self.device.width));
+        for (src, dst) in samples {
+            self.device.write(self.user.read(src), dst);
+        }
+    }
+}
+
+// Unpack `S24_3LE` samples into little-endian `I24` samples, four at a time so that each group is
+// handled with three 32-bit loads and four 32-bit stores which the compiler can vectorise.
+//
+// Returns the remaining samples that did not fill a group of four.
+fn unpack_i24<'a, 'b>(device: &'a [u8], user: &'b mut [u8]) -> (&'a [u8], &'b mut [u8]) {
+    let groups = (device.len() / 12).min(user.len() / 16);
+    let (device, device_rest) = device.split_at(groups * 12);
+    let (user, user_rest) = user.split_at_mut(groups * 16);
+    for (src, dst) in device.chunks_exact(12).zip(user.chunks_exact_mut(16)) {
+        let w0 = u32::from_le_bytes([src[0], src[1], src[2], src[3]]);
+        let w1 = u32::from_le_bytes([src[4], src[5], src[6], src[7]]);
+        let w2 = u32::from_le_bytes([src[8], src[9], src[10], src[11]]);
+        let samples = [w0 << 8, (w0 >> 16) | (w1 << 16), (w1 >> 8) | (w2 << 24), w2];
+        for (sample, dst) in samples.iter().zip(dst.chunks_exact_mut(4)) {
+            // Each sample now sits in the upper 24 bits; shift it down to sign-extend it.
+            dst.copy_from_slice(&((*sample as i32) >> 8).to_le_bytes());
+        }
+    }
+    (device_rest, user_rest)
+}
+
+// The inverse of `unpack_i24`.
+fn pack_i24<'a, 'b>(user: &'a [u8], device: &'b mut [u8]) -> (&'a [u8], &'b mut [u8]) {
+    let groups = (user.len() / 16).min(device.len() / 12);
+    let (user, user_rest) = user.split_at(groups * 16);
+    let (device, device_rest) = device.split_at_mut(groups * 12);
+    for (src, dst) in user.chunks_exact(16).zip(device.chunks_exact_mut(12)) {
+        let s0 = u32::from_le_bytes([src[0], src[1], src[2], src[3]]) & 0xff_ffff;
+        let s1 = u32::from_le_bytes([src[4], src[5], src[6], src[7]]) & 0xff_ffff;
+        let s2 = u32::from_le_bytes([src[8], src[9], src[10], src[11]]) & 0xff_ffff;
+        let s3 = u32::from_le_bytes([src[12], src[13], src[14], src[15]]) & 0xff_ffff;
+        dst[0..4].copy_from_slice(&(s0 | (s1 << 24)).to_le_bytes());
+        dst[4..8].copy_from_slice(&((s1 >> 8) | (s2 << 16)).to_le_bytes());
+        dst[8..12].copy_from_slice(&((s2 >> 16) | (s3 << 8)).to_le_bytes());
+    }
+    (user_rest, device_rest)
+}
+
+#[test]
+fn test_codec_round_trip() {
+    use alsa::pcm::Format;
+
+    let codec = Codec::new(Format::S243LE, SampleFormat::I24).unwrap();
+    let device = [0x56, 0x34, 0x12, 0xff, 0xff, 0xff, 0x00, 0x00, 0x80];
+    let mut user = [0u8; 12];
+    codec.decode(&device, &mut user);
+    let samples: Vec<i32> = user
+        .chunks_exact(4)
+        .map(|b| i32::from_ne_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(samples, [0x123456, -1, -(1 << 23)]);
+
+    let mut encoded = [0u8; 9];
+    codec.encode(&user, &mut encoded);
+    assert_eq!(encoded, device);
+}
+
+#[test]
+fn test_codec_scales_narrow_formats() {
+    use alsa::pcm::Format;
+
+    // Full scale 20-bit and 18-bit samples must map to full scale 24-bit samples.
+    let s20 = Codec::new(Format::S203LE, SampleFormat::I24).unwrap();
+    let mut user = [0u8; 4];
+    s20.decode(&[0xff, 0xff, 0x07], &mut user);
+    assert_eq!(i32::from_ne_bytes(user), 0x7ffff0);
+
+    let s18 = Codec::new(Format::S183LE, SampleFormat::I24).unwrap();
+    s18.decode(&[0x00, 0x00, 0x02], &mut user);
+    assert_eq!(i32::from_ne_bytes(user), -(1 << 23));
+
+    // Unsigned offset binary keeps its origin when widened.
+    let u20 = Codec::new(Format::U203LE, SampleFormat::U24).unwrap();
+    u20.decode(&[0x00, 0x00, 0x08], &mut user);
+    assert_eq!(i32::from_ne_bytes(user), 1 << 23);
+
+    // A 24-bit sample in a 4-byte container is sign-extended over the padding byte.
+    let s24 = Codec::new(Format::S24LE, SampleFormat::I24).unwrap();
+    let mut device = [0u8; 4];
+    s24.encode(&(-2i32).to_ne_bytes(), &mut device);
+    assert_eq!(device, [0xfe, 0xff, 0xff, 0xff]);
+}
+
+#[test]
+fn test_codec_byte_order() {
+    use alsa::pcm::Format;
+
+    let (native, foreign, foreign_bytes) = if cfg!(target_endian = "big") {
+        (Format::S16BE, Format::S16LE, 0x1234i16.to_le_bytes())
+    } else {
+        (Format::S16LE, Format::S16BE, 0x1234i16.to_be_bytes())
+    };
+    assert_eq!(Codec::new(native, SampleFormat::I16), None);
+
+    let s16 = Codec::new(foreign, SampleFormat::I16).unwrap();
+    let mut user = [0u8; 2];
+    s16.decode(&foreign_bytes, &mut user);
+    assert_eq!(i16::from_ne_bytes(user), 0x1234);
+
+    let f32_be = Codec::new(Format::FloatBE, SampleFormat::F32);
+    let mut device = [0u8; 4];
+    match f32_be {
+        Some(codec) => codec.encode(&0.5f32.to_ne_bytes(), &mut device),
+        None => device = 0.5f32.to_ne_bytes(),
+    }
+    assert_eq!(device, 0.5f32.to_be_bytes());
+
+    let s24_3be = Codec::new(Format::S243BE, SampleFormat::I24).unwrap();
+    let mut user = [0u8; 4];
+    s24_3be.decode(&[0x80, 0x00, 0x01], &mut user);
+    assert_eq!(i32::from_ne_bytes(user), -(1 << 23) + 1);
+
+    // Packed samples in the native byte order are handed over untouched.
+    let native_s24_3 = if cfg!(target_endian = "big") {
+        Format::S243BE
+    } else {
+        Format::S243LE
+    };
+    assert_eq!(Codec::new(native_s24_3, SampleFormat::I24Packed), None);
+}
+
+#[test]
+fn test_packed_i24_matches_generic() {
+    use alsa::pcm::Format;
+
+    let codec = Codec::new(Format::S243LE, SampleFormat::I24).unwrap();
+    let generic = Codec {
+        packed_i24: false,
+        ..codec
+    };
+
+    // 11 samples, so that both the groups of four and the remainder are exercised.
+    let device: Vec<u8> = (0..33u32).map(|i| (i * 97 + 13) as u8).collect();
+    let mut expected = vec![0u8; 44];
+    let mut actual = vec![0u8; 44];
+    generic.decode(&device, &mut expected);
+    codec.decode(&device, &mut actual);
+    assert_eq!(actual, expected);
+
+    let user = actual;
+    let mut expected = vec![0u8; 33];
+    let mut actual = vec![0u8; 33];
+    generic.encode(&user, &mut expected);
+    codec.encode(&user, &mut actual);
+    assert_eq!(actual, expected);
+    assert_eq!(actual, device);
+}
@@ -1,19 +1,71 @@
 use super::alsa;
-use super::{Device, DeviceHandles};
+use super::Device;
 use crate::{BackendSpecificError, DevicesError};
-use std::sync::{Arc, Mutex};
+
+/// Whether a PCM plays back audio, captures it or both, as advertised by ALSA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PcmDirection {
+    Playback,
+    Capture,
+    Both,
+}
+
+/// How the PCMs of sound cards are accessed, see [`HostOptions`](super::HostOptions).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CardAccess {
+    /// Through `hw` PCMs, which expose the actual capabilities of the hardware and pass samples
+    /// through untouched, e.g. for bit-exact capture.
+    #[default]
+    Hw,
+    /// Through `plughw` PCMs, which add ALSA's plug layer on top of `hw`. It converts the sample
+    /// formats, rates and channel counts the hardware doesn't support. This is convenient, but
+    /// hides what the hardware actually does, and manipulates the audio under your feet.
+    PlugHw,
+    /// Through both, as sibling devices whose names end with "(hw)" and "(plughw)".
+    Both,
+}
 
 /// ALSA's implementation for `Devices`.
 pub struct Devices {
-    builtin_pos: usize,
-    card_iter: alsa::card::Iter,
+    pcms: std::vec::IntoIter<Pcm>,
 }
 
 impl Devices {
     pub fn new() -> Result<Self, DevicesError> {
+        Self::with_card_access(CardAccess::default())
+    }
+
+    /// The devices of a host whose card PCMs are accessed through `card_access`.
+    pub fn with_card_access(card_access: CardAccess) -> Result<Self, DevicesError> {
+        let hints = alsa::device_name::HintIter::new_str(None, "pcm")?;
+        let mut hints: Vec<Pcm> = hints.filter_map(Pcm::from_hint).collect();
+
+        // The builtins come first, and are listed even if the configuration has no hint for them,
+        // as long as they exist.
+        let mut pcms: Vec<Pcm> = BUILTINS
+            .iter()
+            .filter_map(
+                |&name| match hints.iter().position(|hint| hint.pcm_id == name) {
+                    Some(i) => Some(hints.remove(i)),
+                    None => Pcm::probe(name),
+                },
+            )
+            .collect();
+        // The `hw` and `plughw` PCMs are listed per card instead, along with their subdevices.
+        pcms.extend(
+            hints
+                .into_iter()
+                .filter(|hint| card_of(&hint.pcm_id).is_none()),
+        );
+        for card in alsa::card::Iter::new() {
+            let Ok(card) = card else { continue };
+            if let Ok(card_pcms) = card_pcms(&card, card_access) {
+                pcms.extend(card_pcms);
+            }
+        }
+
         Ok(Devices {
-            builtin_pos: 0,
-            card_iter: alsa::card::Iter::new(),
+            pcms: pcms.into_iter(),
         })
     }
 }
@@ -27,58 +79,233 @@ impl Iterator for Devices {
     type Item = Device;
 
     fn next(&mut self) -> Option<Device> {
-        while self.builtin_pos < BUILTINS.len() {
-            let pos = self.builtin_pos;
-            self.builtin_pos += 1;
-            let name = BUILTINS[pos];
-
-            if let Ok(handles) = DeviceHandles::open(name) {
-                return Some(Device {
-                    name: name.to_string(),
-                    pcm_id: name.to_string(),
-                    handles: Arc::new(Mutex::new(handles)),
-                });
-            }
+        self.pcms.next().map(Pcm::into_device)
+    }
+}
+
+// A PCM found by enumeration. It is only opened once its configs are queried or a stream is
+// built, so that listing devices doesn't keep them busy.
+pub(super) struct Pcm {
+    pub name: String,
+    pub pcm_id: String,
+    pub description: Option<String>,
+    pub direction: PcmDirection,
+}
+
+impl Pcm {
+    // PCMs defined in the configuration are named after their id, as their descriptions are
+    // shared by the different PCMs of a card, e.g. "HDA Intel PCH, ALC892 Analog".
+    fn from_hint(hint: alsa::device_name::Hint) -> Option<Self> {
+        let pcm_id = hint.name?;
+        Some(Pcm {
+            name: pcm_id.clone(),
+            pcm_id,
+            description: hint.desc,
+            direction: match hint.direction {
+                Some(alsa::Direction::Playback) => PcmDirection::Playback,
+                Some(alsa::Direction::Capture) => PcmDirection::Capture,
+                None => PcmDirection::Both,
+            },
+        })
+    }
+
+    // The PCM `pcm_id`, if it exists, for PCMs that the configuration gives no hint for.
+    //
+    // Without a hint, the only way to tell whether the PCM exists is to open it. It is closed
+    // right away, and one that is busy exists all the same.
+    fn probe(pcm_id: &str) -> Option<Self> {
+        let exists = [alsa::Direction::Playback, alsa::Direction::Capture]
+            .into_iter()
+            .any(|dir| match alsa::PCM::new(pcm_id, dir, true) {
+                Ok(_) => true,
+                Err(err) => err.errno() == libc::EBUSY,
+            });
+        exists.then(|| Pcm {
+            name: pcm_id.to_owned(),
+            pcm_id: pcm_id.to_owned(),
+            description: None,
+            direction: PcmDirection::Both,
+        })
+    }
+
+    fn into_device(self) -> Device {
+        Device {
+            name: self.name,
+            pcm_id: self.pcm_id,
+            description: self.description,
+            direction: self.direction,
+            buffer_params: Default::default(),
         }
+    }
+}
 
-        loop {
-            let res = self.card_iter.next()?;
-            let Ok(card) = res else { continue };
+// The PCMs of `card` accessed through `card_access`, e.g. `hw:0,3`, followed by each of their
+// subdevices if they have several, e.g. `hw:0,3,1`.
+//
+// They are named after the card and the PCM, e.g. "HDA Intel PCH, HDMI 0", and described by the
+// long name of the card.
+pub(super) fn card_pcms(
+    card: &alsa::Card,
+    card_access: CardAccess,
+) -> Result<Vec<Pcm>, alsa::Error> {
+    let ctl = alsa::Ctl::from_card(card, false)?;
+    let card_info = ctl.card_info()?;
+    let card_name = card_info.get_name()?;
+    let description = card_info.get_longname()?;
+    let index = card.get_index();
 
-            let ctl_id = format!("hw:{}", card.get_index());
-            let Ok(ctl) = alsa::Ctl::new(&ctl_id, false) else {
-                continue;
-            };
-            let Ok(cardinfo) = ctl.card_info() else {
-                continue;
-            };
-            let Ok(card_name) = cardinfo.get_name() else {
+    // The plugins each PCM is listed with, along with the suffix of their names.
+    let plugins: &[(&str, &str)] = match card_access {
+        CardAccess::Hw => &[("hw", "")],
+        CardAccess::PlugHw => &[("plughw", "")],
+        CardAccess::Both => &[("hw", " (hw)"), ("plughw", " (plughw)")],
+    };
+
+    let mut pcms = Vec::new();
+    let mut push = |name: &str, args: String, direction| {
+        for (plugin, suffix) in plugins {
+            pcms.push(Pcm {
+                name: format!("{}{}", name, suffix),
+                pcm_id: format!("{}:{}", plugin, args),
+                description: Some(description.to_owned()),
+                direction,
+            });
+        }
+    };
+    for device in alsa::ctl::DeviceIter::new(&ctl) {
+        let device = device as u32;
+        let Some((direction, info)) = pcm_info(&ctl, device, 0) else {
+            continue;
+        };
+        let name = format!("{}, {}", card_name, info.get_name()?);
+        push(&name, format!("{},{}", index, device), direction);
+
+        let subdevices = info.get_subdevices_count();
+        if subdevices < 2 {
+            continue;
+        }
+        for subdevice in 0..subdevices {
+            let Some((direction, info)) = pcm_info(&ctl, device, subdevice) else {
                 continue;
             };
+            push(
+                &format!("{} ({})", name, info.get_subdevice_name()?),
+                format!("{},{},{}", index, device, subdevice),
+                direction,
+            );
+        }
+    }
+    Ok(pcms)
+}
+
+// The directions a subdevice of a card supports, along with its info for one of them.
+fn pcm_info(
+    ctl: &alsa::Ctl,
+    device: u32,
+    subdevice: u32,
+) -> Option<(PcmDirection, alsa::pcm::Info)> {
+    let playback = ctl.pcm_info(device, subdevice, alsa::Direction::Playback);
+    let capture = ctl.pcm_info(device, subdevice, alsa::Direction::Capture);
+    match (playback, capture) {
+        (Ok(info), Ok(_)) => Some((PcmDirection::Both, info)),
+        (Ok(info), Err(_)) => Some((PcmDirection::Playback, info)),
+        (Err(_), Ok(info)) => Some((PcmDirection::Capture, info)),
+        (Err(_), Err(_)) => None,
+    }
+}
+
+// The stable id of the device of `pcm_id`, see `DeviceTrait::id`.
+//
+// The card of a `hw` or `plughw` PCM is identified by the USB port it is plugged into, or by its
+// id string otherwise, e.g. `hw:USB=0000:00:14.0-2` or `hw:CARD=PCH` for `hw:1`. The id string
+// alone doesn't tell apart identical USB cards, which ALSA numbers in the order they appeared.
+// Other PCMs don't belong to a specific card, and are identified by their PCM id.
+pub(super) fn device_id(pcm_id: &str) -> Result<String, alsa::Error> {
+    let Some(card) = card_of(pcm_id) else {
+        return Ok(pcm_id.to_owned());
+    };
+    let (plugin, args) = pcm_id.split_once(':').unwrap();
+    let ctl = alsa::Ctl::new(&format!("hw:{}", card), false)?;
+    let mut id = format!("{}:{}", plugin, card_key(&ctl.card_info()?)?);
+    for arg in args.split(',').skip(1) {
+        id.push(',');
+        id.push_str(arg);
+    }
+    Ok(id)
+}
 
-            // Using plughw adds the ALSA plug layer, which can do sample type conversion,
-            // sample rate convertion, ...
-            // It is convenient, but at the same time not suitable for pro-audio as it hides
-            // the actual device capabilities and perform audio manipulation under your feet,
-            // for example sample rate conversion, sample format conversion, adds dummy channels,
-            // ...
-            // For now, many hardware only support 24bit / 3 bytes, which isn't yet supported by
-            // cpal. So we have to enable plughw (unfortunately) for maximum compatibility.
-            const USE_PLUGHW: bool = false;
-            let pcm_id = if USE_PLUGHW {
-                format!("plughw:{}", card.get_index())
-            } else {
-                ctl_id
+// The device with the given id, as returned by `DeviceTrait::id`, named like in enumeration
+// through `card_access`.
+pub(super) fn device_by_id(id: &str, card_access: CardAccess) -> Option<Device> {
+    let pcm = match id.split_once(':') {
+        Some((plugin, args)) if plugin == "hw" || plugin == "plughw" => {
+            let (key, rest) = match args.split_once(',') {
+                Some((key, rest)) => (key, Some(rest)),
+                None => (args, None),
             };
-            if let Ok(handles) = DeviceHandles::open(&pcm_id) {
-                return Some(Device {
-                    name: card_name.to_string(),
-                    pcm_id: pcm_id.to_string(),
-                    handles: Arc::new(Mutex::new(handles)),
-                });
+            // Find the card the key stands for.
+            let (card, info) = alsa::card::Iter::new()
+                .filter_map(Result::ok)
+                .find_map(|card| {
+                    let ctl = alsa::Ctl::from_card(&card, false).ok()?;
+                    let info = ctl.card_info().ok()?;
+                    let matches = card_key(&info).ok()? == key;
+                    matches.then_some((card, info))
+                })?;
+            let mut pcm_id = format!("{}:{}", plugin, card.get_index());
+            if let Some(rest) = rest {
+                pcm_id.push(',');
+                pcm_id.push_str(rest);
+            }
+            // Named like in enumeration if it is listed there, and after the card otherwise.
+            let listed = card_pcms(&card, card_access)
+                .ok()?
+                .into_iter()
+                .find(|pcm| pcm.pcm_id == pcm_id);
+            match listed {
+                Some(pcm) => pcm,
+                None => Pcm {
+                    name: info.get_name().ok()?.to_owned(),
+                    description: Some(info.get_longname().ok()?.to_owned()),
+                    ..Pcm::probe(&pcm_id)?
+                },
+            }
+        }
+        _ => {
+            let hint = alsa::device_name::HintIter::new_str(None, "pcm")
+                .ok()?
+                .filter_map(Pcm::from_hint)
+                .find(|pcm| pcm.pcm_id == id);
+            match hint {
+                Some(pcm) => pcm,
+                None => Pcm::probe(id)?,
             }
         }
+    };
+    Some(pcm.into_device())
+}
+
+// The part of a device id that identifies its card, see `device_id`.
+fn card_key(info: &alsa::ctl::CardInfo) -> Result<String, alsa::Error> {
+    // USB cards have long names like "Vendor Product at usb-0000:00:14.0-2, high speed".
+    let longname = info.get_longname()?;
+    let usb_path = longname
+        .split_once(" at usb-")
+        .and_then(|(_, location)| location.split(',').next());
+    match usb_path {
+        Some(path) => Ok(format!("USB={}", path)),
+        None => Ok(format!("CARD={}", info.get_id()?)),
+    }
+}
+
+// The card of a `hw` or `plughw` PCM id, e.g. `0` for `hw:0,3` or `PCH` for `hw:CARD=PCH,DEV=0`.
+pub(super) fn card_of(pcm_id: &str) -> Option<&str> {
+    let (plugin, args) = pcm_id.split_once(':')?;
+    if plugin != "hw" && plugin != "plughw" {
+        return None;
     }
+    let card = args.split(',').next()?;
+    Some(card.strip_prefix("CARD=").unwrap_or(card))
 }
 
 #[inline]
@@ -86,7 +313,9 @@ pub fn default_input_device() -> Option<Device> {
     Some(Device {
         name: "default".to_owned(),
         pcm_id: "default".to_owned(),
-        handles: Arc::new(Mutex::new(Default::default())),
+        description: None,
+        direction: PcmDirection::Both,
+        buffer_params: Default::default(),
     })
 }
 
@@ -95,7 +324,9 @@ pub fn default_output_device() -> Option<Device> {
     Some(Device {
         name: "default".to_owned(),
         pcm_id: "default".to_owned(),
-        handles: Arc::new(Mutex::new(Default::default())),
+        description: None,
+        direction: PcmDirection::Both,
+        buffer_params: Default::default(),
     })
 }
 
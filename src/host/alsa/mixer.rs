@@ -0,0 +1,414 @@
+//! Hardware volume and mute through the simple mixer controls of a card.
+//!
+//! These are the controls `alsamixer` shows, such as "Master", "PCM" or "Capture", which ALSA
+//! builds from the raw controls of the card's driver.
+
+use super::alsa;
+use super::alsa::mixer::{MilliBel, Selem, SelemChannelId, SelemId};
+use super::alsa::poll::Descriptors;
+use super::{trigger, Device, TriggerReceiver, TriggerSender};
+use crate::BackendSpecificError;
+use std::thread::{self, JoinHandle};
+
+/// A simple mixer control of a card, see [`Mixer::controls`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MixerControl {
+    /// The name of the control, e.g. "Master" or "Mic Boost".
+    pub name: String,
+    /// Tells apart the controls sharing a name, e.g. the "IEC958" switches of several HDMI
+    /// outputs. Usually 0.
+    pub index: u32,
+}
+
+/// The side of a control to act on, as some controls have both a playback and a capture volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MixerDirection {
+    Playback,
+    Capture,
+}
+
+/// The range of the volume of a control, see [`Mixer::volume_range`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VolumeRange {
+    /// The lowest raw value of the volume.
+    pub min: i64,
+    /// The highest raw value of the volume.
+    pub max: i64,
+    /// The gains in dB that `min` and `max` stand for, unless the driver doesn't tell.
+    pub db: Option<(f32, f32)>,
+}
+
+/// The simple mixer controls of the card of a device, see [`Device::mixer`].
+pub struct Mixer {
+    // The name of the control device the mixer is attached to, e.g. `hw:0`.
+    ctl_name: String,
+    mixer: alsa::Mixer,
+}
+
+/// Watches the controls of a mixer until it is dropped, see [`Mixer::watch`].
+pub struct MixerWatcher {
+    trigger: TriggerSender,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for MixerWatcher {
+    fn drop(&mut self) {
+        self.trigger.wakeup();
+        self.thread.take().unwrap().join().unwrap();
+    }
+}
+
+impl Device {
+    /// The mixer of this device's card, or of the default card for devices that don't belong to
+    /// a specific one, such as `default`.
+    ///
+    /// Sound servers may provide the default mixer instead, whose controls act on their own
+    /// volume rather than the hardware's.
+    pub fn mixer(&self) -> Result<Mixer, BackendSpecificError> {
+        Mixer::open(&ctl_name(&self.pcm_id))
+    }
+}
+
+impl Mixer {
+    fn open(ctl_name: &str) -> Result<Self, BackendSpecificError> {
+        let mixer = alsa::Mixer::new(ctl_name, true)?;
+        Ok(Mixer {
+            ctl_name: ctl_name.to_owned(),
+            mixer,
+        })
+    }
+
+    /// The controls of the mixer, in the order ALSA lists them.
+    pub fn controls(&self) -> Vec<MixerControl> {
+        controls(&self.mixer)
+    }
+
+    /// Whether `control` has a volume in `direction`.
+    pub fn has_volume(&self, control: &MixerControl, direction: MixerDirection) -> bool {
+        self.selem(control).is_ok_and(|selem| match direction {
+            MixerDirection::Playback => selem.has_playback_volume(),
+            MixerDirection::Capture => selem.has_capture_volume(),
+        })
+    }
+
+    /// Whether `control` can be muted in `direction`.
+    pub fn has_switch(&self, control: &MixerControl, direction: MixerDirection) -> bool {
+        self.selem(control).is_ok_and(|selem| match direction {
+            MixerDirection::Playback => selem.has_playback_switch(),
+            MixerDirection::Capture => selem.has_capture_switch(),
+        })
+    }
+
+    /// The range of the volume of `control` in `direction`.
+    pub fn volume_range(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+    ) -> Result<VolumeRange, BackendSpecificError> {
+        let selem = self.volume_selem(control, direction)?;
+        let ((min, max), (min_db, max_db)) = match direction {
+            MixerDirection::Playback => (
+                selem.get_playback_volume_range(),
+                selem.get_playback_db_range(),
+            ),
+            MixerDirection::Capture => (
+                selem.get_capture_volume_range(),
+                selem.get_capture_db_range(),
+            ),
+        };
+        // ALSA reports an empty dB range if the driver has none.
+        let db = (min_db != max_db).then(|| (min_db.to_db(), max_db.to_db()));
+        Ok(VolumeRange { min, max, db })
+    }
+
+    /// The raw volume of `control` in `direction`, within its [range](Self::volume_range).
+    ///
+    /// For controls with several channels, this is the volume of the first one.
+    pub fn volume(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+    ) -> Result<i64, BackendSpecificError> {
+        let selem = self.volume_selem(control, direction)?;
+        let volume = match direction {
+            MixerDirection::Playback => selem.get_playback_volume(SelemChannelId::mono())?,
+            MixerDirection::Capture => selem.get_capture_volume(SelemChannelId::mono())?,
+        };
+        Ok(volume)
+    }
+
+    /// The gain of `control` in `direction`, in dB.
+    ///
+    /// For controls with several channels, this is the gain of the first one.
+    pub fn volume_db(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+    ) -> Result<f32, BackendSpecificError> {
+        let selem = self.volume_selem(control, direction)?;
+        let db = match direction {
+            MixerDirection::Playback => selem.get_playback_vol_db(SelemChannelId::mono())?,
+            MixerDirection::Capture => selem.get_capture_vol_db(SelemChannelId::mono())?,
+        };
+        Ok(db.to_db())
+    }
+
+    /// Set the raw volume of every channel of `control` in `direction`.
+    pub fn set_volume(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+        volume: i64,
+    ) -> Result<(), BackendSpecificError> {
+        let selem = self.volume_selem(control, direction)?;
+        match direction {
+            MixerDirection::Playback => selem.set_playback_volume_all(volume)?,
+            MixerDirection::Capture => selem.set_capture_volume_all(volume)?,
+        }
+        Ok(())
+    }
+
+    /// Set the gain of every channel of `control` in `direction`, in dB.
+    ///
+    /// Controls only take discrete steps, and the gain is rounded down to the nearest one so
+    /// that it is never louder than requested.
+    pub fn set_volume_db(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+        db: f32,
+    ) -> Result<(), BackendSpecificError> {
+        let selem = self.volume_selem(control, direction)?;
+        let db = MilliBel::from_db(db);
+        match direction {
+            MixerDirection::Playback => selem.set_playback_db_all(db, alsa::Round::Floor)?,
+            MixerDirection::Capture => selem.set_capture_db_all(db, alsa::Round::Floor)?,
+        }
+        Ok(())
+    }
+
+    /// Whether `control` is muted in `direction`, i.e. its switch is off.
+    ///
+    /// For controls with several channels, this is the switch of the first one.
+    pub fn is_muted(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+    ) -> Result<bool, BackendSpecificError> {
+        let selem = self.switch_selem(control, direction)?;
+        let switch = match direction {
+            MixerDirection::Playback => selem.get_playback_switch(SelemChannelId::mono())?,
+            MixerDirection::Capture => selem.get_capture_switch(SelemChannelId::mono())?,
+        };
+        Ok(switch == 0)
+    }
+
+    /// Mute or unmute every channel of `control` in `direction`.
+    pub fn set_muted(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+        muted: bool,
+    ) -> Result<(), BackendSpecificError> {
+        let selem = self.switch_selem(control, direction)?;
+        let switch = i32::from(!muted);
+        match direction {
+            MixerDirection::Playback => selem.set_playback_switch_all(switch)?,
+            MixerDirection::Capture => selem.set_capture_switch_all(switch)?,
+        }
+        Ok(())
+    }
+
+    /// Call `callback` with each control whose volume or switch changes, e.g. through another
+    /// application, until the returned watcher is dropped.
+    ///
+    /// The callback runs on a thread of its own, which opens the mixer again.
+    pub fn watch<F>(&self, mut callback: F) -> Result<MixerWatcher, BackendSpecificError>
+    where
+        F: FnMut(MixerControl) + Send + 'static,
+    {
+        let mixer = alsa::Mixer::new(&self.ctl_name, true)?;
+        let (trigger, rx) = trigger();
+        let thread = thread::Builder::new()
+            .name("cpal_alsa_mixer_watch".to_owned())
+            .spawn(move || watch_worker(rx, mixer, &mut callback))
+            .unwrap();
+        Ok(MixerWatcher {
+            trigger,
+            thread: Some(thread),
+        })
+    }
+
+    // The element of `control`, with the events received so far applied to its values.
+    fn selem(&self, control: &MixerControl) -> Result<Selem<'_>, BackendSpecificError> {
+        self.mixer.handle_events()?;
+        let id = SelemId::new(&control.name, control.index);
+        self.mixer
+            .find_selem(&id)
+            .ok_or_else(|| BackendSpecificError {
+                description: format!(
+                    "No mixer control named '{}' with index {}",
+                    control.name, control.index
+                ),
+            })
+    }
+
+    fn volume_selem(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+    ) -> Result<Selem<'_>, BackendSpecificError> {
+        if !self.has_volume(control, direction) {
+            return Err(missing(control, direction, "volume"));
+        }
+        self.selem(control)
+    }
+
+    fn switch_selem(
+        &self,
+        control: &MixerControl,
+        direction: MixerDirection,
+    ) -> Result<Selem<'_>, BackendSpecificError> {
+        if !self.has_switch(control, direction) {
+            return Err(missing(control, direction, "switch"));
+        }
+        self.selem(control)
+    }
+}
+
+fn missing(control: &MixerControl, direction: MixerDirection, what: &str) -> BackendSpecificError {
+    let direction = match direction {
+        MixerDirection::Playback => "playback",
+        MixerDirection::Capture => "capture",
+    };
+    BackendSpecificError {
+        description: format!(
+            "The mixer control '{}' has no {} {}",
+            control.name, direction, what
+        ),
+    }
+}
+
+fn controls(mixer: &alsa::Mixer) -> Vec<MixerControl> {
+    mixer
+        .iter()
+        .filter_map(Selem::new)
+        .filter_map(|selem| {
+            let id = selem.get_id();
+            Some(MixerControl {
+                name: id.get_name().ok()?.to_owned(),
+                index: id.get_index(),
+            })
+        })
+        .collect()
+}
+
+// The volumes and switches of every channel of every control, to tell which ones an event
+// changed. ALSA only tells which controls changed through element callbacks, which the `alsa`
+// crate doesn't expose.
+fn snapshot(mixer: &alsa::Mixer) -> Vec<(MixerControl, Vec<i64>)> {
+    let mut snapshot = Vec::new();
+    for selem in mixer.iter().filter_map(Selem::new) {
+        let id = selem.get_id();
+        let Ok(name) = id.get_name() else { continue };
+        let mut values = Vec::new();
+        for &channel in SelemChannelId::all() {
+            if selem.has_playback_channel(channel) {
+                values.extend(selem.get_playback_volume(channel).ok());
+                values.extend(selem.get_playback_switch(channel).ok().map(i64::from));
+            }
+            if selem.has_capture_channel(channel) {
+                values.extend(selem.get_capture_volume(channel).ok());
+                values.extend(selem.get_capture_switch(channel).ok().map(i64::from));
+            }
+        }
+        let control = MixerControl {
+            name: name.to_owned(),
+            index: id.get_index(),
+        };
+        snapshot.push((control, values));
+    }
+    snapshot
+}
+
+fn watch_worker(rx: TriggerReceiver, mixer: alsa::Mixer, callback: &mut dyn FnMut(MixerControl)) {
+    let mut values = snapshot(&mixer);
+    let mut descriptors = Vec::new();
+    // Once the card is removed, only the watcher being dropped is waited for.
+    let mut removed = false;
+    loop {
+        descriptors.clear();
+        descriptors.push(libc::pollfd {
+            fd: rx.0,
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        if !removed {
+            if let Ok(fds) = Descriptors::get(&mixer) {
+                descriptors.extend(fds);
+            }
+        }
+
+        if alsa::poll::poll(&mut descriptors, -1).is_err() {
+            // Interrupted by a signal, or out of memory. Try again.
+            continue;
+        }
+        if descriptors[0].revents != 0 {
+            // The watcher has been dropped.
+            rx.clear_pipe();
+            return;
+        }
+        if removed {
+            continue;
+        }
+        match mixer.revents(&descriptors[1..]) {
+            Ok(flags) if !flags.intersects(alsa::poll::Flags::ERR | alsa::poll::Flags::HUP) => {}
+            _ => {
+                removed = true;
+                continue;
+            }
+        }
+        if mixer.handle_events().is_err() {
+            removed = true;
+            continue;
+        }
+
+        let new_values = snapshot(&mixer);
+        for (control, value) in &new_values {
+            if !values.iter().any(|(c, v)| c == control && v == value) {
+                callback(control.clone());
+            }
+        }
+        values = new_values;
+    }
+}
+
+// The name of the control device of the card of `pcm_id`, which the mixer is attached to.
+//
+// The card is the `CARD` argument of the PCM, or its first positional argument, as for
+// `hw:1,3`, `front:CARD=USB,DEV=0` or `sysdefault:PCH`. PCMs without a card use the default one.
+fn ctl_name(pcm_id: &str) -> String {
+    let card = pcm_id.split_once(':').and_then(|(_, args)| {
+        let mut args = args.split(',');
+        let first = args.clone().next().filter(|arg| !arg.contains('='));
+        args.find_map(|arg| arg.strip_prefix("CARD=")).or(first)
+    });
+    match card {
+        Some(card) if !card.is_empty() => format!("hw:{}", card),
+        _ => "default".to_owned(),
+    }
+}
+
+#[test]
+fn test_ctl_name() {
+    assert_eq!(ctl_name("hw:1,3"), "hw:1");
+    assert_eq!(ctl_name("plughw:CARD=PCH,DEV=0"), "hw:PCH");
+    assert_eq!(ctl_name("front:CARD=USB,DEV=0"), "hw:USB");
+    assert_eq!(ctl_name("hdmi:CARD=NVidia,DEV=0"), "hw:NVidia");
+    assert_eq!(ctl_name("sysdefault:CARD=X"), "hw:X");
+    assert_eq!(ctl_name("surround51:DEV=0,CARD=2"), "hw:2");
+    assert_eq!(ctl_name("iec958:1"), "hw:1");
+    assert_eq!(ctl_name("default"), "default");
+    assert_eq!(ctl_name("null"), "default");
+    assert_eq!(ctl_name("hdmi:DEV=1"), "default");
+}
@@ -2,36 +2,125 @@ extern crate alsa;
 extern crate libc;
 
 use self::alsa::poll::Descriptors;
+use crate::host::error_queue::{error_queue, ErrorReceiver, ErrorSender};
+use crate::host::stats::StatsRecorder;
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
-    BackendSpecificError, BufferSize, BuildStreamError, ChannelCount, Data,
-    DefaultStreamConfigError, DeviceNameError, DevicesError, InputCallbackInfo, OutputCallbackInfo,
-    PauseStreamError, PlayStreamError, SampleFormat, SampleRate, StreamConfig, StreamError,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    ALaw, BackendSpecificError, BufferSize, BuildStreamError, ChannelCount, Data,
+    DefaultStreamConfigError, DeviceEvent, DeviceId, DeviceNameError, DeviceWatcher, DevicesError,
+    DuplexCallbackInfo, DuplexStreamConfig, DuplexStreamTimestamp, FrameCount, InputCallbackInfo,
+    MuLaw, NegotiatedStreamConfig, OutputCallbackInfo, PauseStreamError, PlayStreamError,
+    SampleFormat, SampleRate, SizedSample, StreamConfig, StreamError, StreamStats,
+    SupportedBufferSize, SupportedSampleRates, SupportedStreamConfig, SupportedStreamConfigRange,
+    SupportedStreamConfigSet, SupportedStreamConfigsError, WatchDevicesError, I24, I48, U24, U48,
 };
 use std::cell::Cell;
 use std::cmp;
 use std::convert::TryInto;
-use std::sync::{Arc, Mutex};
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::vec::IntoIter as VecIntoIter;
 
-pub use self::enumerate::{default_input_device, default_output_device, Devices};
+use self::codec::Codec;
+pub use self::enumerate::{
+    default_input_device, default_output_device, CardAccess, Devices, PcmDirection,
+};
+pub use self::mixer::{Mixer, MixerControl, MixerDirection, MixerWatcher, VolumeRange};
+pub use self::params::{BufferLayout, BufferParams};
+pub use self::passthrough::{PassthroughConfig, PassthroughOutput};
+use self::shared::MixedStream;
+pub use self::shared::{SharedStream, Sharing};
 
 pub type SupportedInputConfigs = VecIntoIter<SupportedStreamConfigRange>;
 pub type SupportedOutputConfigs = VecIntoIter<SupportedStreamConfigRange>;
 
+mod codec;
 mod enumerate;
+mod mixer;
+mod params;
+mod passthrough;
+mod shared;
+mod watch;
+
+// The ALSA formats that can be streamed, along with the sample format each is exposed as.
+//
+// Several ALSA formats may be exposed as the same sample format, e.g. both byte orders of a
+// format. Formats that need no conversion are preferred, followed by the order of this table.
+// Formats whose layout differs from the exposed sample format are converted by a `Codec` in the
+// stream worker.
+const FORMATS: [(SampleFormat, alsa::pcm::Format); 34] = [
+    // Formats that callers have to opt into are listed first, so that the default config prefers
+    // the others.
+    (SampleFormat::I24Packed, alsa::pcm::Format::S243LE),
+    (SampleFormat::I24Packed, alsa::pcm::Format::S243BE),
+    (SampleFormat::MuLaw, alsa::pcm::Format::MuLaw),
+    (SampleFormat::ALaw, alsa::pcm::Format::ALaw),
+    (SampleFormat::I8, alsa::pcm::Format::S8),
+    (SampleFormat::U8, alsa::pcm::Format::U8),
+    (SampleFormat::I16, alsa::pcm::Format::S16LE),
+    (SampleFormat::I16, alsa::pcm::Format::S16BE),
+    (SampleFormat::U16, alsa::pcm::Format::U16LE),
+    (SampleFormat::U16, alsa::pcm::Format::U16BE),
+    (SampleFormat::I24, alsa::pcm::Format::S243LE),
+    (SampleFormat::I24, alsa::pcm::Format::S243BE),
+    (SampleFormat::I24, alsa::pcm::Format::S24LE),
+    (SampleFormat::I24, alsa::pcm::Format::S24BE),
+    (SampleFormat::I24, alsa::pcm::Format::S203LE),
+    (SampleFormat::I24, alsa::pcm::Format::S203BE),
+    //SND_PCM_FORMAT_S20_LE, (not exposed by the `alsa` crate)
+    //SND_PCM_FORMAT_S20_BE, (not exposed by the `alsa` crate)
+    (SampleFormat::I24, alsa::pcm::Format::S183LE),
+    (SampleFormat::I24, alsa::pcm::Format::S183BE),
+    (SampleFormat::U24, alsa::pcm::Format::U243LE),
+    (SampleFormat::U24, alsa::pcm::Format::U243BE),
+    (SampleFormat::U24, alsa::pcm::Format::U24LE),
+    (SampleFormat::U24, alsa::pcm::Format::U24BE),
+    (SampleFormat::U24, alsa::pcm::Format::U203LE),
+    (SampleFormat::U24, alsa::pcm::Format::U203BE),
+    //SND_PCM_FORMAT_U20_LE, (not exposed by the `alsa` crate)
+    //SND_PCM_FORMAT_U20_BE, (not exposed by the `alsa` crate)
+    (SampleFormat::U24, alsa::pcm::Format::U183LE),
+    (SampleFormat::U24, alsa::pcm::Format::U183BE),
+    (SampleFormat::I32, alsa::pcm::Format::S32LE),
+    (SampleFormat::I32, alsa::pcm::Format::S32BE),
+    (SampleFormat::U32, alsa::pcm::Format::U32LE),
+    (SampleFormat::U32, alsa::pcm::Format::U32BE),
+    (SampleFormat::F32, alsa::pcm::Format::FloatLE),
+    (SampleFormat::F32, alsa::pcm::Format::FloatBE),
+    (SampleFormat::F64, alsa::pcm::Format::Float64LE),
+    (SampleFormat::F64, alsa::pcm::Format::Float64BE),
+    //SND_PCM_FORMAT_IEC958_SUBFRAME_LE, (bitstreams are passed through as `I16`, see `passthrough`)
+    //SND_PCM_FORMAT_IEC958_SUBFRAME_BE, (bitstreams are passed through as `I16`, see `passthrough`)
+    //SND_PCM_FORMAT_IMA_ADPCM,
+    //SND_PCM_FORMAT_MPEG,
+    //SND_PCM_FORMAT_GSM,
+    //SND_PCM_FORMAT_SPECIAL,
+];
 
 /// The default linux, dragonfly, freebsd and netbsd host type.
 #[derive(Debug)]
-pub struct Host;
+pub struct Host {
+    options: HostOptions,
+}
+
+/// Options of the ALSA host, see [`Host::with_options`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HostOptions {
+    /// How the PCMs of sound cards are accessed. Defaults to [`CardAccess::Hw`].
+    pub card_access: CardAccess,
+}
 
 impl Host {
     pub fn new() -> Result<Self, crate::HostUnavailable> {
-        Ok(Host)
+        Self::with_options(HostOptions::default())
+    }
+
+    /// A host whose devices are enumerated according to `options`.
+    pub fn with_options(options: HostOptions) -> Result<Self, crate::HostUnavailable> {
+        Ok(Host { options })
     }
 }
 
@@ -45,7 +134,7 @@ impl HostTrait for Host {
     }
 
     fn devices(&self) -> Result<Self::Devices, DevicesError> {
-        Devices::new()
+        Devices::with_card_access(self.options.card_access)
     }
 
     fn default_input_device(&self) -> Option<Self::Device> {
@@ -55,6 +144,20 @@ impl HostTrait for Host {
     fn default_output_device(&self) -> Option<Self::Device> {
         default_output_device()
     }
+
+    fn device_by_id(&self, id: &DeviceId) -> Result<Option<Self::Device>, DevicesError> {
+        Ok(enumerate::device_by_id(
+            id.as_str(),
+            self.options.card_access,
+        ))
+    }
+
+    fn watch_devices<F>(&self, callback: F) -> Result<DeviceWatcher, WatchDevicesError>
+    where
+        F: FnMut(DeviceEvent) + Send + 'static,
+    {
+        watch::watch_devices(self.options.card_access, callback)
+    }
 }
 
 impl DeviceTrait for Device {
@@ -66,6 +169,11 @@ impl DeviceTrait for Device {
         Device::name(self)
     }
 
+    fn id(&self) -> Result<DeviceId, DeviceNameError> {
+        let id = enumerate::device_id(&self.pcm_id).map_err(BackendSpecificError::from)?;
+        Ok(DeviceId::from(id))
+    }
+
     fn supported_input_configs(
         &self,
     ) -> Result<Self::SupportedInputConfigs, SupportedStreamConfigsError> {
@@ -78,6 +186,18 @@ impl DeviceTrait for Device {
         Device::supported_output_configs(self)
     }
 
+    fn supported_input_config_sets(
+        &self,
+    ) -> Result<Vec<SupportedStreamConfigSet>, SupportedStreamConfigsError> {
+        self.supported_config_sets(alsa::Direction::Capture)
+    }
+
+    fn supported_output_config_sets(
+        &self,
+    ) -> Result<Vec<SupportedStreamConfigSet>, SupportedStreamConfigsError> {
+        self.supported_config_sets(alsa::Direction::Playback)
+    }
+
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
         Device::default_input_config(self)
     }
@@ -100,13 +220,14 @@ impl DeviceTrait for Device {
     {
         let stream_inner =
             self.build_stream_inner(conf, sample_format, alsa::Direction::Capture)?;
-        let stream = Stream::new_input(
+        stream_inner.channel.start()?;
+        let stream = PcmStream::new_input(
             Arc::new(stream_inner),
             data_callback,
             error_callback,
             timeout,
         );
-        Ok(stream)
+        Ok(stream.into())
     }
 
     fn build_output_stream_raw<D, E>(
@@ -122,14 +243,55 @@ impl DeviceTrait for Device {
         E: FnMut(StreamError) + Send + 'static,
     {
         let stream_inner =
-            self.build_stream_inner(conf, sample_format, alsa::Direction::Playback)?;
-        let stream = Stream::new_output(
+            match self.build_stream_inner(conf, sample_format, alsa::Direction::Playback) {
+                // The PCM is exclusive, and held by shared streams, which this stream joins.
+                Err(BuildStreamError::DeviceNotAvailable) if self.is_mixed() => {
+                    return self.build_mixed_output_stream(
+                        conf,
+                        sample_format,
+                        data_callback,
+                        error_callback,
+                    );
+                }
+                result => result?,
+            };
+        let stream = PcmStream::new_output(
             Arc::new(stream_inner),
             data_callback,
             error_callback,
             timeout,
         );
-        Ok(stream)
+        Ok(stream.into())
+    }
+
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        conf: &DuplexStreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let capture =
+            self.build_stream_inner(&conf.input(), sample_format, alsa::Direction::Capture)?;
+        let playback =
+            self.build_stream_inner(&conf.output(), sample_format, alsa::Direction::Playback)?;
+        // Linked channels are started and stopped together by ALSA, sample-synchronously if both
+        // are on the same card. Not every plugin supports this.
+        let linked = playback.channel.link(&capture.channel).is_ok();
+        let stream = PcmStream::new_duplex(
+            Arc::new(capture),
+            Arc::new(playback),
+            linked,
+            data_callback,
+            error_callback,
+            timeout,
+        );
+        Ok(stream.into())
     }
 }
 
@@ -177,69 +339,13 @@ impl Drop for TriggerReceiver {
     }
 }
 
-#[derive(Default)]
-struct DeviceHandles {
-    playback: Option<alsa::PCM>,
-    capture: Option<alsa::PCM>,
-}
-
-impl DeviceHandles {
-    /// Create `DeviceHandles` for `name` and try to open a handle for both
-    /// directions. Returns `Ok` if either direction is opened successfully.
-    fn open(pcm_id: &str) -> Result<Self, alsa::Error> {
-        let mut handles = Self::default();
-        let playback_err = handles.try_open(pcm_id, alsa::Direction::Playback).err();
-        let capture_err = handles.try_open(pcm_id, alsa::Direction::Capture).err();
-        if let Some(err) = capture_err.and(playback_err) {
-            Err(err)
-        } else {
-            Ok(handles)
-        }
-    }
-
-    /// Get a mutable reference to the `Option` for a specific `stream_type`.
-    /// If the `Option` is `None`, the `alsa::PCM` will be opened and placed in
-    /// the `Option` before returning. If `handle_mut()` returns `Ok` the contained
-    /// `Option` is guaranteed to be `Some(..)`.
-    fn try_open(
-        &mut self,
-        pcm_id: &str,
-        stream_type: alsa::Direction,
-    ) -> Result<&mut Option<alsa::PCM>, alsa::Error> {
-        let handle = match stream_type {
-            alsa::Direction::Playback => &mut self.playback,
-            alsa::Direction::Capture => &mut self.capture,
-        };
-
-        if handle.is_none() {
-            *handle = Some(alsa::pcm::PCM::new(pcm_id, stream_type, true)?);
-        }
-
-        Ok(handle)
-    }
-
-    /// Get a mutable reference to the `alsa::PCM` handle for a specific `stream_type`.
-    /// If the handle is not yet opened, it will be opened and stored in `self`.
-    fn get_mut(
-        &mut self,
-        pcm_id: &str,
-        stream_type: alsa::Direction,
-    ) -> Result<&mut alsa::PCM, alsa::Error> {
-        Ok(self.try_open(pcm_id, stream_type)?.as_mut().unwrap())
-    }
-
-    /// Take ownership of the `alsa::PCM` handle for a specific `stream_type`.
-    /// If the handle is not yet opened, it will be opened and returned.
-    fn take(&mut self, name: &str, stream_type: alsa::Direction) -> Result<alsa::PCM, alsa::Error> {
-        Ok(self.try_open(name, stream_type)?.take().unwrap())
-    }
-}
-
 #[derive(Clone)]
 pub struct Device {
     name: String,
     pcm_id: String,
-    handles: Arc<Mutex<DeviceHandles>>,
+    description: Option<String>,
+    direction: PcmDirection,
+    buffer_params: BufferParams,
 }
 
 impl Device {
@@ -249,12 +355,13 @@ impl Device {
         sample_format: SampleFormat,
         stream_type: alsa::Direction,
     ) -> Result<StreamInner, BuildStreamError> {
-        let handle_result = self
-            .handles
-            .lock()
-            .unwrap()
-            .take(&self.pcm_id, stream_type)
-            .map_err(|e| (e, e.errno()));
+        // A zero period count or size would divide by zero when deriving the layout.
+        if self.buffer_params.periods == Some(0) || self.buffer_params.period_size == Some(0) {
+            return Err(BuildStreamError::InvalidArgument);
+        }
+
+        let handle_result =
+            alsa::pcm::PCM::new(&self.pcm_id, stream_type, true).map_err(|e| (e, e.errno()));
 
         let handle = match handle_result {
             Err((_, libc::EBUSY)) => return Err(BuildStreamError::DeviceNotAvailable),
@@ -262,8 +369,10 @@ impl Device {
             Err((e, _)) => return Err(e.into()),
             Ok(handle) => handle,
         };
-        let can_pause = set_hw_params_from_format(&handle, conf, sample_format)?;
-        let period_len = set_sw_params_from_format(&handle, conf, stream_type)?;
+        let (alsa_format, can_pause) =
+            set_hw_params_from_format(&handle, conf, sample_format, &self.buffer_params)?;
+        let buffer_layout = set_sw_params_from_format(&handle, stream_type, &self.buffer_params)?;
+        let period_len = buffer_layout.avail_min as usize * conf.channels as usize;
 
         handle.prepare()?;
 
@@ -282,19 +391,21 @@ impl Device {
             _ => None,
         };
 
-        if let alsa::Direction::Capture = stream_type {
-            handle.start()?;
-        }
-
+        let period = frames_to_duration(buffer_layout.period_size as usize, conf.sample_rate);
         let stream_inner = StreamInner {
             dropping: Cell::new(false),
+            paused: AtomicBool::new(false),
             channel: handle,
+            stream_type,
             sample_format,
+            codec: Codec::new(alsa_format, sample_format),
             num_descriptors,
             conf: conf.clone(),
             period_len,
+            buffer_layout,
             can_pause,
             creation_instant,
+            stats: StatsRecorder::new(period),
         };
 
         Ok(stream_inner)
@@ -305,14 +416,28 @@ impl Device {
         Ok(self.name.clone())
     }
 
-    fn supported_configs(
+    /// The description ALSA gives of the PCM, if any, e.g. "HDA Intel PCH, ALC892 Analog\nFront
+    /// output / input".
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Whether the PCM plays back audio, captures it or both, as advertised by ALSA.
+    ///
+    /// PCMs that don't advertise a direction are reported as [`PcmDirection::Both`], even though
+    /// they may support only one direction.
+    pub fn direction(&self) -> PcmDirection {
+        self.direction
+    }
+
+    fn supported_config_sets(
         &self,
         stream_t: alsa::Direction,
-    ) -> Result<VecIntoIter<SupportedStreamConfigRange>, SupportedStreamConfigsError> {
-        let mut guard = self.handles.lock().unwrap();
-        let handle_result = guard
-            .get_mut(&self.pcm_id, stream_t)
-            .map_err(|e| (e, e.errno()));
+    ) -> Result<Vec<SupportedStreamConfigSet>, SupportedStreamConfigsError> {
+        // Query a temporary handle, which is closed again on return so that listing devices
+        // doesn't keep them busy.
+        let handle_result =
+            alsa::pcm::PCM::new(&self.pcm_id, stream_t, true).map_err(|e| (e, e.errno()));
 
         let handle = match handle_result {
             Err((_, libc::ENOENT)) | Err((_, libc::EBUSY)) => {
@@ -323,54 +448,13 @@ impl Device {
             Ok(handle) => handle,
         };
 
-        let hw_params = alsa::pcm::HwParams::any(handle)?;
-
-        // TODO: check endianness
-        const FORMATS: [(SampleFormat, alsa::pcm::Format); 9] = [
-            (SampleFormat::I8, alsa::pcm::Format::S8),
-            (SampleFormat::U8, alsa::pcm::Format::U8),
-            (SampleFormat::I16, alsa::pcm::Format::S16LE),
-            //SND_PCM_FORMAT_S16_BE,
-            (SampleFormat::U16, alsa::pcm::Format::U16LE),
-            //SND_PCM_FORMAT_U16_BE,
-            //SND_PCM_FORMAT_S24_LE,
-            //SND_PCM_FORMAT_S24_BE,
-            //SND_PCM_FORMAT_U24_LE,
-            //SND_PCM_FORMAT_U24_BE,
-            (SampleFormat::I32, alsa::pcm::Format::S32LE),
-            //SND_PCM_FORMAT_S32_BE,
-            (SampleFormat::U32, alsa::pcm::Format::U32LE),
-            //SND_PCM_FORMAT_U32_BE,
-            (SampleFormat::F32, alsa::pcm::Format::FloatLE),
-            //SND_PCM_FORMAT_FLOAT_BE,
-            (SampleFormat::F64, alsa::pcm::Format::Float64LE),
-            //SND_PCM_FORMAT_FLOAT64_BE,
-            //SND_PCM_FORMAT_IEC958_SUBFRAME_LE,
-            //SND_PCM_FORMAT_IEC958_SUBFRAME_BE,
-            //SND_PCM_FORMAT_MU_LAW,
-            //SND_PCM_FORMAT_A_LAW,
-            //SND_PCM_FORMAT_IMA_ADPCM,
-            //SND_PCM_FORMAT_MPEG,
-            //SND_PCM_FORMAT_GSM,
-            //SND_PCM_FORMAT_SPECIAL,
-            //SND_PCM_FORMAT_S24_3LE,
-            (SampleFormat::I24, alsa::pcm::Format::S243LE),
-            //SND_PCM_FORMAT_S24_3BE,
-            //SND_PCM_FORMAT_U24_3LE,
-            //SND_PCM_FORMAT_U24_3BE,
-            //SND_PCM_FORMAT_S20_3LE,
-            //SND_PCM_FORMAT_S20_3BE,
-            //SND_PCM_FORMAT_U20_3LE,
-            //SND_PCM_FORMAT_U20_3BE,
-            //SND_PCM_FORMAT_S18_3LE,
-            //SND_PCM_FORMAT_S18_3BE,
-            //SND_PCM_FORMAT_U18_3LE,
-            //SND_PCM_FORMAT_U18_3BE,
-        ];
+        let hw_params = alsa::pcm::HwParams::any(&handle)?;
 
         let mut supported_formats = Vec::new();
         for &(sample_format, alsa_format) in FORMATS.iter() {
-            if hw_params.test_format(alsa_format).is_ok() {
+            if !supported_formats.contains(&sample_format)
+                && hw_params.test_format(alsa_format).is_ok()
+            {
                 supported_formats.push(sample_format);
             }
         }
@@ -379,40 +463,32 @@ impl Device {
         let max_rate = hw_params.get_rate_max()?;
 
         let sample_rates = if min_rate == max_rate || hw_params.test_rate(min_rate + 1).is_ok() {
-            vec![(min_rate, max_rate)]
-        } else {
-            const RATES: [libc::c_uint; 13] = [
-                5512, 8000, 11025, 16000, 22050, 32000, 44100, 48000, 64000, 88200, 96000, 176400,
-                192000,
-            ];
-
-            let mut rates = Vec::new();
-            for &rate in RATES.iter() {
-                if hw_params.test_rate(rate).is_ok() {
-                    rates.push((rate, rate));
-                }
-            }
-
-            if rates.is_empty() {
-                vec![(min_rate, max_rate)]
-            } else {
-                rates
+            SupportedSampleRates::Range {
+                min: SampleRate(min_rate),
+                max: SampleRate(max_rate),
             }
+        } else {
+            let nearest = |rate| {
+                hw_params
+                    .clone()
+                    .set_rate_near(rate, alsa::ValueOr::Nearest)
+            };
+            let rates = discrete_values(nearest, min_rate, max_rate)?;
+            SupportedSampleRates::Discrete(rates.into_iter().map(SampleRate).collect())
         };
 
         let min_channels = hw_params.get_channels_min()?;
         let max_channels = hw_params.get_channels_max()?;
 
-        let max_channels = cmp::min(max_channels, 32); // TODO: limiting to 32 channels or too much stuff is returned
-        let supported_channels = (min_channels..max_channels + 1)
-            .filter_map(|num| {
-                if hw_params.test_channels(num).is_ok() {
-                    Some(num as ChannelCount)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
+        // Plugins such as `plug` accept practically any number of channels.
+        let max_channels = cmp::min(max_channels, ChannelCount::MAX as u32);
+        let channel_ranges =
+            if min_channels >= max_channels || hw_params.test_channels(min_channels + 1).is_ok() {
+                vec![(min_channels, max_channels)]
+            } else {
+                let nearest = |channels| hw_params.clone().set_channels_near(channels);
+                consecutive_runs(&discrete_values(nearest, min_channels, max_channels)?)
+            };
 
         let min_buffer_size = hw_params.get_buffer_size_min()?;
         let max_buffer_size = hw_params.get_buffer_size_max()?;
@@ -422,24 +498,32 @@ impl Device {
             max: max_buffer_size as u32,
         };
 
-        let mut output = Vec::with_capacity(
-            supported_formats.len() * supported_channels.len() * sample_rates.len(),
-        );
+        let mut output = Vec::with_capacity(supported_formats.len() * channel_ranges.len());
         for &sample_format in supported_formats.iter() {
-            for &channels in supported_channels.iter() {
-                for &(min_rate, max_rate) in sample_rates.iter() {
-                    output.push(SupportedStreamConfigRange {
-                        channels,
-                        min_sample_rate: SampleRate(min_rate),
-                        max_sample_rate: SampleRate(max_rate),
-                        buffer_size: buffer_size_range,
-                        sample_format,
-                    });
-                }
+            for &(min_channels, max_channels) in channel_ranges.iter() {
+                output.push(SupportedStreamConfigSet::new(
+                    min_channels as ChannelCount,
+                    max_channels as ChannelCount,
+                    sample_rates.clone(),
+                    buffer_size_range,
+                    sample_format,
+                ));
             }
         }
 
-        Ok(output.into_iter())
+        Ok(output)
+    }
+
+    fn supported_configs(
+        &self,
+        stream_t: alsa::Direction,
+    ) -> Result<VecIntoIter<SupportedStreamConfigRange>, SupportedStreamConfigsError> {
+        let sets = self.supported_config_sets(stream_t)?;
+        let ranges: Vec<_> = sets
+            .iter()
+            .flat_map(SupportedStreamConfigSet::ranges)
+            .collect();
+        Ok(ranges.into_iter())
     }
 
     fn supported_input_configs(
@@ -455,43 +539,36 @@ impl Device {
     }
 
     // ALSA does not offer default stream formats, so instead we compare all supported formats by
-    // the `SupportedStreamConfigRange::cmp_default_heuristics` order and select the greatest.
+    // the `SupportedStreamConfigSet::cmp_default_heuristics` order and select the greatest.
     fn default_config(
         &self,
         stream_t: alsa::Direction,
     ) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
-        let mut formats: Vec<_> = {
-            match self.supported_configs(stream_t) {
-                Err(SupportedStreamConfigsError::DeviceNotAvailable) => {
-                    return Err(DefaultStreamConfigError::DeviceNotAvailable);
-                }
-                Err(SupportedStreamConfigsError::InvalidArgument) => {
-                    // this happens sometimes when querying for input and output capabilities, but
-                    // the device supports only one
-                    return Err(DefaultStreamConfigError::StreamTypeNotSupported);
-                }
-                Err(SupportedStreamConfigsError::BackendSpecific { err }) => {
-                    return Err(err.into());
-                }
-                Ok(fmts) => fmts.collect(),
+        let mut formats = match self.supported_config_sets(stream_t) {
+            Err(SupportedStreamConfigsError::DeviceNotAvailable) => {
+                return Err(DefaultStreamConfigError::DeviceNotAvailable);
+            }
+            Err(SupportedStreamConfigsError::InvalidArgument) => {
+                // this happens sometimes when querying for input and output capabilities, but
+                // the device supports only one
+                return Err(DefaultStreamConfigError::StreamTypeNotSupported);
             }
+            Err(SupportedStreamConfigsError::BackendSpecific { err }) => {
+                return Err(err.into());
+            }
+            Ok(fmts) => fmts,
         };
 
         formats.sort_by(|a, b| a.cmp_default_heuristics(b));
 
-        match formats.into_iter().next_back() {
-            Some(f) => {
-                let min_r = f.min_sample_rate;
-                let max_r = f.max_sample_rate;
-                let mut format = f.with_max_sample_rate();
-                const HZ_44100: SampleRate = SampleRate(44_100);
-                if min_r <= HZ_44100 && HZ_44100 <= max_r {
-                    format.sample_rate = HZ_44100;
-                }
-                Ok(format)
-            }
-            None => Err(DefaultStreamConfigError::StreamTypeNotSupported),
-        }
+        const HZ_44100: SampleRate = SampleRate(44_100);
+        formats
+            .pop()
+            .and_then(|f| {
+                f.try_with_sample_rate(HZ_44100)
+                    .or_else(|| f.with_max_sample_rate())
+            })
+            .ok_or(DefaultStreamConfigError::StreamTypeNotSupported)
     }
 
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
@@ -508,9 +585,16 @@ struct StreamInner {
     // (e.g. broken due to a disconnected device).
     dropping: Cell<bool>,
 
+    // Whether the user asked for the stream to be paused. The worker thread pauses or resumes the
+    // channel when it notices that this changed.
+    paused: AtomicBool,
+
     // The ALSA channel.
     channel: alsa::pcm::PCM,
 
+    // Whether the channel is used for playback or capture.
+    stream_type: alsa::Direction,
+
     // When converting between file descriptors and `snd_pcm_t`, this is the number of
     // file descriptors that this `snd_pcm_t` uses.
     num_descriptors: usize,
@@ -518,15 +602,20 @@ struct StreamInner {
     // Format of the samples.
     sample_format: SampleFormat,
 
+    // Converts between the device's sample layout and `sample_format`, if they differ.
+    codec: Option<Codec>,
+
     // The configuration used to open this stream.
     conf: StreamConfig,
 
     // Minimum number of samples to put in the buffer.
     period_len: usize,
 
-    #[allow(dead_code)]
-    // Whether or not the hardware supports pausing the stream.
-    // TODO: We need an API to expose this. See #197, #284.
+    // The layout of the ring buffer negotiated with ALSA.
+    buffer_layout: BufferLayout,
+
+    // Whether or not the hardware supports pausing the stream. If not, pausing drops the stream and
+    // resuming prepares it again.
     can_pause: bool,
 
     // In the case that the device does not return valid timestamps via `get_htstamp`, this field
@@ -538,6 +627,9 @@ struct StreamInner {
     // If this field is `None` then the elapsed duration between `get_trigger_htstamp` and
     // `get_htstamp` is used.
     creation_instant: Option<std::time::Instant>,
+
+    // Statistics updated by the worker thread.
+    stats: StatsRecorder,
 }
 
 // Assume that the ALSA library is built with thread safe option.
@@ -549,7 +641,16 @@ enum StreamType {
     Output,
 }
 
-pub struct Stream {
+pub struct Stream(StreamKind);
+
+enum StreamKind {
+    // A stream with a PCM of its own.
+    Pcm(PcmStream),
+    // An output stream added to the mixer holding an exclusive PCM, see `build_output_stream_raw`.
+    Mixed(MixedStream),
+}
+
+struct PcmStream {
     /// The high-priority audio processing thread calling callbacks.
     /// Option used for moving out in destructor.
     thread: Option<JoinHandle<()>>,
@@ -557,8 +658,14 @@ pub struct Stream {
     /// Handle to the underlying stream for playback controls.
     inner: Arc<StreamInner>,
 
+    /// For duplex streams, the capture side, while `inner` is the playback side.
+    capture: Option<Arc<StreamInner>>,
+
     /// Used to signal to stop processing.
     trigger: TriggerSender,
+
+    /// Errors raised by the processing thread, passed to the error callback on another thread.
+    errors: ErrorReceiver<WorkerError>,
 }
 
 struct StreamWorkerContext {
@@ -566,21 +673,45 @@ struct StreamWorkerContext {
     buffer: Vec<u8>,
     temp_buffer: Vec<u8>,
     poll_timeout: i32,
+    pause_state: PauseState,
+}
+
+// How the worker thread left the channel after the user paused the stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PauseState {
+    Running,
+    // Paused with `snd_pcm_pause`, resumed where it stopped.
+    HardwarePaused,
+    // Stopped with `snd_pcm_drop`, which discards the pending frames.
+    Dropped,
 }
 
 impl StreamWorkerContext {
-    fn new(poll_timeout: &Option<Duration>) -> Self {
+    fn new(poll_timeout: &Option<Duration>, stream: &StreamInner) -> Self {
         let poll_timeout: i32 = if let Some(d) = poll_timeout {
             d.as_millis().try_into().unwrap()
         } else {
             -1
         };
 
+        // Reserve enough space for a full hardware buffer up front, so that the buffers are never
+        // reallocated on the audio thread once the stream is running.
+        let buffer_samples = match stream.channel.get_params() {
+            Ok((buffer_frames, _)) => buffer_frames as usize * stream.conf.channels as usize,
+            Err(_) => 0,
+        };
+        let sample_size = stream.sample_format.sample_size();
+        let (buffer_len, temp_buffer_len) = match &stream.codec {
+            Some(codec) => (codec.device_width(), sample_size),
+            None => (sample_size, 0),
+        };
+
         Self {
-            descriptors: Vec::new(),
-            buffer: Vec::new(),
-            temp_buffer: Vec::new(),
+            descriptors: Vec::with_capacity(stream.num_descriptors + 1),
+            buffer: Vec::with_capacity(buffer_len * buffer_samples),
+            temp_buffer: Vec::with_capacity(temp_buffer_len * buffer_samples),
             poll_timeout,
+            pause_state: PauseState::Running,
         }
     }
 }
@@ -589,16 +720,16 @@ fn input_stream_worker(
     rx: TriggerReceiver,
     stream: &StreamInner,
     data_callback: &mut (dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static),
-    error_callback: &mut (dyn FnMut(StreamError) + Send + 'static),
+    errors: &mut ErrorSender<WorkerError>,
     timeout: Option<Duration>,
 ) {
     boost_current_thread_priority(stream.conf.buffer_size, stream.conf.sample_rate);
 
-    let mut ctxt = StreamWorkerContext::new(&timeout);
+    let mut ctxt = StreamWorkerContext::new(&timeout, stream);
     loop {
         let flow =
             poll_descriptors_and_prepare_buffer(&rx, stream, &mut ctxt).unwrap_or_else(|err| {
-                error_callback(err.into());
+                errors.send(err);
                 PollDescriptorsFlow::Continue
             });
 
@@ -607,8 +738,12 @@ fn input_stream_worker(
                 continue;
             }
             PollDescriptorsFlow::XRun => {
+                if let Some(err) = xrun_error(stream, stream.stats.frames()) {
+                    stream.stats.record_xrun();
+                    errors.send(WorkerError::Stream(err));
+                }
                 if let Err(err) = stream.channel.prepare() {
-                    error_callback(err.into());
+                    errors.send(err.into());
                 }
                 continue;
             }
@@ -624,15 +759,10 @@ fn input_stream_worker(
                     StreamType::Input,
                     "expected input stream, but polling descriptors indicated output",
                 );
-                if let Err(err) = process_input(
-                    stream,
-                    &mut ctxt.buffer,
-                    &mut ctxt.temp_buffer,
-                    status,
-                    delay_frames,
-                    data_callback,
-                ) {
-                    error_callback(err.into());
+                if let Err(err) =
+                    process_input(stream, &mut ctxt, status, delay_frames, data_callback)
+                {
+                    errors.send(err);
                 }
             }
         }
@@ -643,24 +773,28 @@ fn output_stream_worker(
     rx: TriggerReceiver,
     stream: &StreamInner,
     data_callback: &mut (dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static),
-    error_callback: &mut (dyn FnMut(StreamError) + Send + 'static),
+    errors: &mut ErrorSender<WorkerError>,
     timeout: Option<Duration>,
 ) {
     boost_current_thread_priority(stream.conf.buffer_size, stream.conf.sample_rate);
 
-    let mut ctxt = StreamWorkerContext::new(&timeout);
+    let mut ctxt = StreamWorkerContext::new(&timeout, stream);
     loop {
         let flow =
             poll_descriptors_and_prepare_buffer(&rx, stream, &mut ctxt).unwrap_or_else(|err| {
-                error_callback(err.into());
+                errors.send(err);
                 PollDescriptorsFlow::Continue
             });
 
         match flow {
             PollDescriptorsFlow::Continue => continue,
             PollDescriptorsFlow::XRun => {
+                if let Some(err) = xrun_error(stream, stream.stats.frames()) {
+                    stream.stats.record_xrun();
+                    errors.send(WorkerError::Stream(err));
+                }
                 if let Err(err) = stream.channel.prepare() {
-                    error_callback(err.into());
+                    errors.send(err.into());
                 }
                 continue;
             }
@@ -678,20 +812,295 @@ fn output_stream_worker(
                 );
                 if let Err(err) = process_output(
                     stream,
-                    &mut ctxt.buffer,
+                    &mut ctxt,
                     status,
                     avail_frames,
                     delay_frames,
                     data_callback,
-                    error_callback,
+                    errors,
                 ) {
-                    error_callback(err.into());
+                    errors.send(err);
                 }
             }
         }
     }
 }
 
+// The channels of a duplex stream, which are started and stopped together.
+struct DuplexChannels<'a> {
+    capture: &'a StreamInner,
+    playback: &'a StreamInner,
+    // Whether the channels are linked with `snd_pcm_link`, so that ALSA starts and stops both
+    // when either is. Otherwise, both are started and stopped in turn.
+    linked: bool,
+}
+
+impl DuplexChannels<'_> {
+    // Prepare both channels, fill the playback buffer with silence and start them.
+    //
+    // Playback then runs a buffer ahead of capture, which leaves the data callback a full buffer
+    // of time to turn the captured frames into output.
+    fn start(&self, output: &mut StreamWorkerContext) -> Result<(), alsa::Error> {
+        let playback = self.playback;
+        playback.channel.prepare()?;
+        if !self.linked {
+            self.capture.channel.prepare()?;
+        }
+
+        let frames = playback.channel.avail()? as usize;
+        resize_buffers(playback, output, frames * playback.conf.channels as usize);
+        fill_equilibrium(&mut user_data(playback, output));
+        if let Some(codec) = &playback.codec {
+            codec.encode(&output.temp_buffer, &mut output.buffer);
+        }
+        playback.channel.io_bytes().writei(&output.buffer)?;
+
+        // Filling the buffer normally starts playback through its start threshold already.
+        if playback.channel.state() != alsa::pcm::State::Running {
+            playback.channel.start()?;
+        }
+        if !self.linked {
+            self.capture.channel.start()?;
+        }
+        Ok(())
+    }
+
+    // Stop both channels, discarding any pending frames.
+    fn stop(&self) -> Result<(), alsa::Error> {
+        self.playback.channel.drop()?;
+        if !self.linked {
+            self.capture.channel.drop()?;
+        }
+        Ok(())
+    }
+}
+
+// Drives both channels of a duplex stream from one thread.
+//
+// Each time capture has a period of frames available, they are read and passed to the data
+// callback along with as many frames of output, which are then written to playback. Pausing stops
+// both channels, and playing starts them again, so that playback is a full buffer ahead again.
+fn duplex_stream_worker(
+    rx: TriggerReceiver,
+    channels: &DuplexChannels,
+    data_callback: &mut (dyn FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static),
+    errors: &mut ErrorSender<WorkerError>,
+    timeout: Option<Duration>,
+) {
+    let DuplexChannels {
+        capture, playback, ..
+    } = *channels;
+    boost_current_thread_priority(playback.conf.buffer_size, playback.conf.sample_rate);
+
+    let mut input = StreamWorkerContext::new(&timeout, capture);
+    let mut output = StreamWorkerContext::new(&timeout, playback);
+    // The channels are only prepared so far, and are started like a resumed stream.
+    input.pause_state = PauseState::Dropped;
+    loop {
+        let flow = match poll_duplex(&rx, channels, &mut input, &mut output) {
+            Ok(PollDescriptorsFlow::Ready {
+                status,
+                avail_frames,
+                delay_frames,
+                ..
+            }) => process_duplex(
+                channels,
+                &mut input,
+                &mut output,
+                status,
+                avail_frames,
+                delay_frames,
+                data_callback,
+            ),
+            flow => flow,
+        };
+
+        match flow {
+            Ok(PollDescriptorsFlow::Return) => return,
+            Ok(PollDescriptorsFlow::XRun) => {
+                // Playback underruns and capture overruns are both reported. Linked channels stop
+                // together, so that an underrun also puts capture in an xrun, which isn't an
+                // overrun of its own. Each incident is recorded once in the stats of the stream,
+                // which are those of playback.
+                let position = playback.stats.frames();
+                let underrun = xrun_error(playback, position);
+                let overrun = match underrun {
+                    Some(_) if channels.linked => None,
+                    _ => xrun_error(capture, position),
+                };
+                if underrun.is_some() || overrun.is_some() {
+                    playback.stats.record_xrun();
+                }
+                for err in underrun.into_iter().chain(overrun) {
+                    errors.send(WorkerError::Stream(err));
+                }
+                if let Err(err) = channels.stop().and_then(|()| channels.start(&mut output)) {
+                    errors.send(err.into());
+                }
+            }
+            Ok(_) => {}
+            Err(err) => errors.send(err),
+        }
+    }
+}
+
+// Like `poll_descriptors_and_prepare_buffer`, but waits for the capture channel of a duplex stream
+// and prepares the buffers of both.
+fn poll_duplex(
+    rx: &TriggerReceiver,
+    channels: &DuplexChannels,
+    input: &mut StreamWorkerContext,
+    output: &mut StreamWorkerContext,
+) -> Result<PollDescriptorsFlow, WorkerError> {
+    let DuplexChannels {
+        capture, playback, ..
+    } = *channels;
+    if playback.dropping.get() {
+        // The stream has been requested to be destroyed.
+        rx.clear_pipe();
+        return Ok(PollDescriptorsFlow::Return);
+    }
+
+    let paused = playback.paused.load(Ordering::Acquire);
+    if paused != (input.pause_state != PauseState::Running) {
+        // The state is switched even if this fails, so that it's not retried right away.
+        if paused {
+            input.pause_state = PauseState::Dropped;
+            channels.stop()?;
+        } else {
+            input.pause_state = PauseState::Running;
+            channels.start(output)?;
+        }
+    }
+
+    let revents = match poll_descriptors(rx, capture, input)? {
+        ControlFlow::Break(flow) => return Ok(flow),
+        ControlFlow::Continue(revents) => revents,
+    };
+    if revents.contains(alsa::poll::Flags::ERR) {
+        return Ok(PollDescriptorsFlow::XRun);
+    }
+    if revents != alsa::poll::Flags::IN {
+        return Ok(PollDescriptorsFlow::Continue);
+    }
+
+    let status = capture.channel.status()?;
+    let (capture_frames, playback_frames) =
+        match (capture.channel.avail(), playback.channel.avail()) {
+            (Err(err), _) | (_, Err(err)) if err.errno() == libc::EPIPE => {
+                return Ok(PollDescriptorsFlow::XRun)
+            }
+            (capture_frames, playback_frames) => (capture_frames?, playback_frames?),
+        };
+    let delay_frames = status.get_delay().max(0) as usize;
+
+    // Only go on if there is at least `capture.period_len` samples.
+    if capture_frames as usize * (capture.conf.channels as usize) < capture.period_len {
+        return Ok(PollDescriptorsFlow::Continue);
+    }
+
+    // Both channels run on the same clock, so playback has room for the captured frames unless
+    // its buffer is smaller.
+    let frames = cmp::min(capture_frames, playback_frames) as usize;
+    resize_buffers(capture, input, frames * capture.conf.channels as usize);
+    resize_buffers(playback, output, frames * playback.conf.channels as usize);
+
+    Ok(PollDescriptorsFlow::Ready {
+        stream_type: StreamType::Input,
+        status,
+        avail_frames: frames,
+        delay_frames,
+    })
+}
+
+// Read `frames` frames from the capture channel of a duplex stream, pass them to the user along
+// with the output buffer, and write that to the playback channel.
+fn process_duplex(
+    channels: &DuplexChannels,
+    input: &mut StreamWorkerContext,
+    output: &mut StreamWorkerContext,
+    status: alsa::pcm::Status,
+    frames: usize,
+    capture_delay_frames: usize,
+    data_callback: &mut (dyn FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static),
+) -> Result<PollDescriptorsFlow, WorkerError> {
+    let DuplexChannels {
+        capture, playback, ..
+    } = *channels;
+    match capture.channel.io_bytes().readi(&mut input.buffer) {
+        Err(err) if err.errno() == libc::EPIPE => return Ok(PollDescriptorsFlow::XRun),
+        res => res?,
+    };
+    if let Some(codec) = &capture.codec {
+        codec.decode(&input.buffer, &mut input.temp_buffer);
+    }
+    let input_data = user_data(capture, input);
+    let mut output_data = user_data(playback, output);
+
+    let rate = playback.conf.sample_rate;
+    let capture_delay = frames_to_duration(capture_delay_frames, rate);
+    let playback_delay = match playback.channel.delay() {
+        Err(err) if err.errno() == libc::EPIPE => return Ok(PollDescriptorsFlow::XRun),
+        res => frames_to_duration(res?.max(0) as usize, rate),
+    };
+    let callback = stream_timestamp(&status, capture.creation_instant)?;
+    let timestamp = DuplexStreamTimestamp {
+        callback,
+        capture: callback
+            .sub(capture_delay)
+            .expect("`capture` is earlier than representation supported by `StreamInstant`"),
+        playback: callback
+            .add(playback_delay)
+            .expect("`playback` occurs beyond representation supported by `StreamInstant`"),
+    };
+    let info = DuplexCallbackInfo { timestamp };
+    playback.stats.record_delay(capture_delay + playback_delay);
+    let started = Instant::now();
+    data_callback(&input_data, &mut output_data, &info);
+    playback.stats.record_callback(frames, started.elapsed());
+
+    if let Some(codec) = &playback.codec {
+        codec.encode(&output.temp_buffer, &mut output.buffer);
+    }
+    match playback.channel.io_bytes().writei(&output.buffer) {
+        Err(err) if err.errno() == libc::EPIPE => Ok(PollDescriptorsFlow::XRun),
+        Err(err) => Err(err.into()),
+        Ok(result) if result != frames => Err(WorkerError::UnexpectedFrameCount {
+            expected: frames,
+            result,
+        }),
+        Ok(_) => Ok(PollDescriptorsFlow::Continue),
+    }
+}
+
+// Fill `data` with silence.
+fn fill_equilibrium(data: &mut Data) {
+    fn fill<T: SizedSample>(data: &mut Data) {
+        if let Some(samples) = data.as_slice_mut::<T>() {
+            samples.fill(T::EQUILIBRIUM);
+        }
+    }
+    match data.sample_format() {
+        SampleFormat::I8 => fill::<i8>(data),
+        SampleFormat::I16 => fill::<i16>(data),
+        SampleFormat::I24 => fill::<I24>(data),
+        SampleFormat::I24Packed => data.bytes_mut().fill(0),
+        SampleFormat::I32 => fill::<i32>(data),
+        SampleFormat::I48 => fill::<I48>(data),
+        SampleFormat::I64 => fill::<i64>(data),
+        SampleFormat::U8 => fill::<u8>(data),
+        SampleFormat::U16 => fill::<u16>(data),
+        SampleFormat::U24 => fill::<U24>(data),
+        SampleFormat::U32 => fill::<u32>(data),
+        SampleFormat::U48 => fill::<U48>(data),
+        SampleFormat::U64 => fill::<u64>(data),
+        SampleFormat::F32 => fill::<f32>(data),
+        SampleFormat::F64 => fill::<f64>(data),
+        SampleFormat::MuLaw => fill::<MuLaw>(data),
+        SampleFormat::ALaw => fill::<ALaw>(data),
+    }
+}
+
 #[cfg(feature = "audio_thread_priority")]
 fn boost_current_thread_priority(buffer_size: BufferSize, sample_rate: SampleRate) {
     use audio_thread_priority::promote_current_thread_to_real_time;
@@ -728,56 +1137,22 @@ fn poll_descriptors_and_prepare_buffer(
     rx: &TriggerReceiver,
     stream: &StreamInner,
     ctxt: &mut StreamWorkerContext,
-) -> Result<PollDescriptorsFlow, BackendSpecificError> {
+) -> Result<PollDescriptorsFlow, WorkerError> {
     if stream.dropping.get() {
         // The stream has been requested to be destroyed.
         rx.clear_pipe();
         return Ok(PollDescriptorsFlow::Return);
     }
 
-    let StreamWorkerContext {
-        ref mut descriptors,
-        ref mut buffer,
-        ref mut temp_buffer,
-        ref poll_timeout,
-    } = *ctxt;
-
-    descriptors.clear();
-
-    // Add the self-pipe for signaling termination.
-    descriptors.push(libc::pollfd {
-        fd: rx.0,
-        events: libc::POLLIN,
-        revents: 0,
-    });
-
-    // Add ALSA polling fds.
-    let len = descriptors.len();
-    descriptors.resize(
-        stream.num_descriptors + len,
-        libc::pollfd {
-            fd: 0,
-            events: 0,
-            revents: 0,
-        },
-    );
-    let filled = stream.channel.fill(&mut descriptors[len..])?;
-    debug_assert_eq!(filled, stream.num_descriptors);
-
-    // Don't timeout, wait forever.
-    let res = alsa::poll::poll(descriptors, *poll_timeout)?;
-    if res == 0 {
-        let description = String::from("`alsa::poll()` spuriously returned");
-        return Err(BackendSpecificError { description });
+    let paused = stream.paused.load(Ordering::Acquire);
+    if paused != (ctxt.pause_state != PauseState::Running) {
+        ctxt.pause_state = set_paused(stream, ctxt.pause_state, paused)?;
     }
 
-    if descriptors[0].revents != 0 {
-        // The stream has been requested to be destroyed.
-        rx.clear_pipe();
-        return Ok(PollDescriptorsFlow::Return);
-    }
-
-    let revents = stream.channel.revents(&descriptors[1..])?;
+    let revents = match poll_descriptors(rx, stream, ctxt)? {
+        ControlFlow::Break(flow) => return Ok(flow),
+        ControlFlow::Continue(revents) => revents,
+    };
     if revents.contains(alsa::poll::Flags::ERR) {
         // POLLERR indicates a serious error. Try to recover by treating it as an XRun
         return Ok(PollDescriptorsFlow::XRun);
@@ -797,10 +1172,12 @@ fn poll_descriptors_and_prepare_buffer(
         res => res,
     }? as usize;
     let delay_frames = match status.get_delay() {
-        // Buffer underrun. TODO: Notify the user.
         d if d < 0 => 0,
         d => d as usize,
     };
+    stream
+        .stats
+        .record_delay(frames_to_duration(delay_frames, stream.conf.sample_rate));
     let available_samples = avail_frames * stream.conf.channels as usize;
 
     // Only go on if there is at least `stream.period_len` samples.
@@ -808,15 +1185,7 @@ fn poll_descriptors_and_prepare_buffer(
         return Ok(PollDescriptorsFlow::Continue);
     }
 
-    // Prepare the data buffer.
-    if stream.sample_format == SampleFormat::I24 {
-        // For S24_3LE format, ALSA uses 3 bytes per sample
-        buffer.resize(3 * available_samples, 0u8);
-        temp_buffer.resize(4 * available_samples, 0u8);
-    } else {
-        let buffer_size = stream.sample_format.sample_size() * available_samples;
-        buffer.resize(buffer_size, 0u8);
-    }
+    resize_buffers(stream, ctxt, available_samples);
 
     Ok(PollDescriptorsFlow::Ready {
         stream_type,
@@ -826,40 +1195,148 @@ fn poll_descriptors_and_prepare_buffer(
     })
 }
 
-// Read input data from ALSA and deliver it to the user.
-fn process_input(
+// Wait until the channel of `stream` has events, which are returned, or until the self-pipe wakes
+// the thread, in which case the flow to follow is returned.
+fn poll_descriptors(
+    rx: &TriggerReceiver,
     stream: &StreamInner,
-    buffer: &mut [u8],
-    temp_buffer: &mut [u8],
-    status: alsa::pcm::Status,
-    delay_frames: usize,
-    data_callback: &mut (dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static),
-) -> Result<(), BackendSpecificError> {
-    stream.channel.io_bytes().readi(buffer)?;
-    let sample_format = stream.sample_format;
+    ctxt: &mut StreamWorkerContext,
+) -> Result<ControlFlow<PollDescriptorsFlow, alsa::poll::Flags>, WorkerError> {
+    let StreamWorkerContext {
+        ref mut descriptors,
+        ref poll_timeout,
+        pause_state,
+        ..
+    } = *ctxt;
+
+    descriptors.clear();
 
-    let (data, len) = if sample_format == SampleFormat::I24 {
-        // Put the 3 bytes into a regular 4 bytes structure
-        let num_samples = buffer.len() / 3;
-        for i in 0..num_samples {
-            let src_offset = i * 3;
-            let dst_offset = i * 4;
+    // Add the self-pipe for signaling termination and pausing.
+    descriptors.push(libc::pollfd {
+        fd: rx.0,
+        events: libc::POLLIN,
+        revents: 0,
+    });
 
-            let src = &buffer[src_offset..src_offset + 3];
-            let dst = &mut temp_buffer[dst_offset..dst_offset + 4];
+    // Add ALSA polling fds, unless the stream is paused and only waits for the pipe.
+    if pause_state == PauseState::Running {
+        let len = descriptors.len();
+        descriptors.resize(
+            stream.num_descriptors + len,
+            libc::pollfd {
+                fd: 0,
+                events: 0,
+                revents: 0,
+            },
+        );
+        let filled = stream.channel.fill(&mut descriptors[len..])?;
+        debug_assert_eq!(filled, stream.num_descriptors);
+    }
 
-            // Fill 3 bytes with the data
-            dst[..3].copy_from_slice(src);
-            // Sign-extend the 24-bit sample to 32 bits
-            dst[3] = if src[2] & 0x80 != 0 { 0xFF } else { 0x00 };
+    // Don't timeout, wait forever.
+    let res = alsa::poll::poll(descriptors, *poll_timeout)?;
+    if res == 0 {
+        if pause_state != PauseState::Running {
+            return Ok(ControlFlow::Break(PollDescriptorsFlow::Continue));
         }
-        (temp_buffer.as_ptr() as *mut (), num_samples)
+        return Err(WorkerError::SpuriousPoll);
+    }
+
+    if descriptors[0].revents != 0 {
+        // The stream has been requested to be destroyed, paused or resumed.
+        rx.clear_pipe();
+        if stream.dropping.get() {
+            return Ok(ControlFlow::Break(PollDescriptorsFlow::Return));
+        }
+        return Ok(ControlFlow::Break(PollDescriptorsFlow::Continue));
+    }
+
+    Ok(ControlFlow::Continue(
+        stream.channel.revents(&descriptors[1..])?,
+    ))
+}
+
+// Size the buffers of `ctxt` to hold `samples` samples of `stream`.
+fn resize_buffers(stream: &StreamInner, ctxt: &mut StreamWorkerContext, samples: usize) {
+    let sample_size = stream.sample_format.sample_size();
+    if let Some(codec) = &stream.codec {
+        // The device buffer uses the device's layout, which is converted to or from the user's
+        // layout held in `temp_buffer`.
+        ctxt.buffer.resize(codec.device_width() * samples, 0u8);
+        ctxt.temp_buffer.resize(sample_size * samples, 0u8);
+    } else {
+        ctxt.buffer.resize(sample_size * samples, 0u8);
+    }
+}
+
+// The buffer of `ctxt` that holds samples in the user's layout, i.e. `temp_buffer` if the device
+// uses a different one.
+fn user_data(stream: &StreamInner, ctxt: &mut StreamWorkerContext) -> Data {
+    let sample_format = stream.sample_format;
+    let buffer = if stream.codec.is_some() {
+        &mut ctxt.temp_buffer
     } else {
-        let len = buffer.len() / sample_format.sample_size();
-        (buffer.as_mut_ptr() as *mut (), len)
+        &mut ctxt.buffer
     };
+    let len = buffer.len() / sample_format.sample_size();
+    unsafe { Data::from_parts(buffer.as_mut_ptr() as *mut (), len, sample_format) }
+}
+
+// Pause or resume the channel of `stream` on the worker thread, returning the new state.
+//
+// Running streams are paused in hardware if it supports that. Otherwise, and for streams that have
+// not started yet or fail to pause, the stream is dropped and then prepared again when resumed.
+// Capture streams are restarted right away, while playback streams start once enough frames have
+// been written.
+fn set_paused(
+    stream: &StreamInner,
+    state: PauseState,
+    paused: bool,
+) -> Result<PauseState, alsa::Error> {
+    let channel = &stream.channel;
+    match (state, paused) {
+        (PauseState::Running, true) => {
+            if stream.can_pause
+                && channel.state() == alsa::pcm::State::Running
+                && channel.pause(true).is_ok()
+            {
+                Ok(PauseState::HardwarePaused)
+            } else {
+                channel.drop()?;
+                Ok(PauseState::Dropped)
+            }
+        }
+        (PauseState::HardwarePaused, false) if channel.pause(false).is_ok() => {
+            Ok(PauseState::Running)
+        }
+        (PauseState::HardwarePaused | PauseState::Dropped, false) => {
+            if state == PauseState::HardwarePaused {
+                channel.drop()?;
+            }
+            channel.prepare()?;
+            if stream.stream_type == alsa::Direction::Capture {
+                channel.start()?;
+            }
+            Ok(PauseState::Running)
+        }
+        (state, _) => Ok(state),
+    }
+}
 
-    let data = unsafe { Data::from_parts(data, len, sample_format) };
+// Read input data from ALSA and deliver it to the user.
+fn process_input(
+    stream: &StreamInner,
+    ctxt: &mut StreamWorkerContext,
+    status: alsa::pcm::Status,
+    delay_frames: usize,
+    data_callback: &mut (dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static),
+) -> Result<(), WorkerError> {
+    stream.channel.io_bytes().readi(&mut ctxt.buffer)?;
+    if let Some(codec) = &stream.codec {
+        codec.decode(&ctxt.buffer, &mut ctxt.temp_buffer);
+    }
+
+    let data = user_data(stream, ctxt);
     let callback = stream_timestamp(&status, stream.creation_instant)?;
     let delay_duration = frames_to_duration(delay_frames, stream.conf.sample_rate);
     let capture = callback
@@ -867,7 +1344,10 @@ fn process_input(
         .expect("`capture` is earlier than representation supported by `StreamInstant`");
     let timestamp = crate::InputStreamTimestamp { callback, capture };
     let info = crate::InputCallbackInfo { timestamp };
+    let started = Instant::now();
     data_callback(&data, &info);
+    let frames = data.len() / stream.conf.channels as usize;
+    stream.stats.record_callback(frames, started.elapsed());
     Ok(())
 }
 
@@ -876,38 +1356,18 @@ fn process_input(
 // Returns `true`
 fn process_output(
     stream: &StreamInner,
-    buffer: &mut [u8],
+    ctxt: &mut StreamWorkerContext,
     status: alsa::pcm::Status,
     available_frames: usize,
     delay_frames: usize,
     data_callback: &mut (dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static),
-    error_callback: &mut dyn FnMut(StreamError),
-) -> Result<(), BackendSpecificError> {
+    errors: &mut ErrorSender<WorkerError>,
+) -> Result<(), WorkerError> {
     {
         // We're now sure that we're ready to write data.
-        let sample_format = stream.sample_format;
-        let data = buffer.as_mut_ptr() as *mut ();
-        let len = if sample_format == SampleFormat::I24 {
-            // For I24, the buffer is sized for 3 bytes per sample
-            buffer.len() / 3
-        } else {
-            buffer.len() / sample_format.sample_size()
-        };
-
-        // For I24 format, we need to provide a temporary buffer with 4 bytes per sample
-        let mut temp_buffer = if sample_format == SampleFormat::I24 {
-            vec![0u8; len * 4]
-        } else {
-            vec![]
-        };
-
-        let (data_ptr, actual_len) = if sample_format == SampleFormat::I24 {
-            (temp_buffer.as_mut_ptr() as *mut (), len)
-        } else {
-            (data, len)
-        };
-
-        let mut data = unsafe { Data::from_parts(data_ptr, actual_len, sample_format) };
+        // If the device uses a different layout, the user writes into the temporary buffer,
+        // which is converted afterwards.
+        let mut data = user_data(stream, ctxt);
         let callback = stream_timestamp(&status, stream.creation_instant)?;
         let delay_duration = frames_to_duration(delay_frames, stream.conf.sample_rate);
         let playback = callback
@@ -915,44 +1375,39 @@ fn process_output(
             .expect("`playback` occurs beyond representation supported by `StreamInstant`");
         let timestamp = crate::OutputStreamTimestamp { callback, playback };
         let info = crate::OutputCallbackInfo { timestamp };
+        let started = Instant::now();
         data_callback(&mut data, &info);
+        stream
+            .stats
+            .record_callback(available_frames, started.elapsed());
 
-        // For I24, pack the 4-byte samples back to 3-byte format for ALSA
-        if sample_format == SampleFormat::I24 {
-            for i in 0..len {
-                let src_offset = i * 4;
-                let dst_offset = i * 3;
-                buffer[dst_offset] = temp_buffer[src_offset];
-                buffer[dst_offset + 1] = temp_buffer[src_offset + 1];
-                buffer[dst_offset + 2] = temp_buffer[src_offset + 2];
-                // Ignore the 4th byte (padding/sign extension)
-            }
+        if let Some(codec) = &stream.codec {
+            codec.encode(&ctxt.temp_buffer, &mut ctxt.buffer);
         }
     }
     loop {
-        match stream.channel.io_bytes().writei(buffer) {
+        match stream.channel.io_bytes().writei(&ctxt.buffer) {
             Err(err) if err.errno() == libc::EPIPE => {
                 // ALSA underrun or overrun.
                 // See https://github.com/alsa-project/alsa-lib/blob/b154d9145f0e17b9650e4584ddfdf14580b4e0d7/src/pcm/pcm.c#L8767-L8770
                 // Even if these recover successfully, they still may cause audible glitches.
-
-                // TODO:
-                //   Should we notify the user about successfully recovered errors?
-                //   Should we notify the user about failures in try_recover, rather than ignoring them?
-                //   (Both potentially not real-time-safe)
-                _ = stream.channel.try_recover(err, true);
+                if let Some(err) = xrun_error(stream, stream.stats.frames()) {
+                    stream.stats.record_xrun();
+                    errors.send(WorkerError::Stream(err));
+                }
+                if let Err(err) = stream.channel.try_recover(err, true) {
+                    errors.send(err.into());
+                }
             }
             Err(err) => {
-                error_callback(err.into());
+                errors.send(err.into());
                 continue;
             }
             Ok(result) if result != available_frames => {
-                let description = format!(
-                    "unexpected number of frames written: expected {}, \
-                     result {} (this should never happen)",
-                    available_frames, result,
-                );
-                error_callback(BackendSpecificError { description }.into());
+                errors.send(WorkerError::UnexpectedFrameCount {
+                    expected: available_frames,
+                    result,
+                });
                 continue;
             }
             _ => {
@@ -963,42 +1418,52 @@ fn process_output(
     Ok(())
 }
 
+// The error describing the xrun that `stream` stopped at, if any.
+//
+// While the stream is stopped, ALSA's status timestamp is the current time and the trigger
+// timestamp is the moment the xrun happened. This doesn't allocate.
+fn xrun_error(stream: &StreamInner, position: u64) -> Option<StreamError> {
+    let status = stream.channel.status().ok()?;
+    if status.get_state() != alsa::pcm::State::XRun {
+        return None;
+    }
+    let lost = timespec_diff_nanos(status.get_htstamp(), status.get_trigger_htstamp());
+    let lost = Duration::from_nanos(lost.max(0) as u64);
+    Some(match stream.stream_type {
+        alsa::Direction::Playback => StreamError::BufferUnderrun { position, lost },
+        alsa::Direction::Capture => StreamError::BufferOverrun { position, lost },
+    })
+}
+
 // Use the elapsed duration since the start of the stream.
 //
 // This ensures positive values that are compatible with our `StreamInstant` representation.
 fn stream_timestamp(
     status: &alsa::pcm::Status,
     creation_instant: Option<std::time::Instant>,
-) -> Result<crate::StreamInstant, BackendSpecificError> {
+) -> Result<crate::StreamInstant, WorkerError> {
     match creation_instant {
         None => {
             let trigger_ts = status.get_trigger_htstamp();
             let ts = status.get_htstamp();
             let nanos = timespec_diff_nanos(ts, trigger_ts);
             if nanos < 0 {
-                let description = format!(
-                    "get_htstamp `{}.{}` was earlier than get_trigger_htstamp `{}.{}`",
-                    ts.tv_sec, ts.tv_nsec, trigger_ts.tv_sec, trigger_ts.tv_nsec
-                );
-                return Err(BackendSpecificError { description });
+                return Err(WorkerError::TimestampBeforeTrigger { ts, trigger_ts });
             }
             Ok(crate::StreamInstant::from_nanos(nanos))
         }
         Some(creation) => {
             let now = std::time::Instant::now();
             let duration = now.duration_since(creation);
-            crate::StreamInstant::from_nanos_i128(duration.as_nanos() as i128).ok_or(
-                BackendSpecificError {
-                    description: "stream duration has exceeded `StreamInstant` representation"
-                        .to_string(),
-                },
-            )
+            crate::StreamInstant::from_nanos_i128(duration.as_nanos() as i128)
+                .ok_or(WorkerError::TimestampOverflow)
         }
     }
 }
 
 // Adapted from `timestamp2ns` here:
 // https://fossies.org/linux/alsa-lib/test/audio_time.c
+#[allow(clippy::unnecessary_cast)] // `time_t` and `c_long` are not `i64` on every target.
 fn timespec_to_nanos(ts: libc::timespec) -> i64 {
     ts.tv_sec as i64 * 1_000_000_000 + ts.tv_nsec as i64
 }
@@ -1017,203 +1482,360 @@ fn frames_to_duration(frames: usize, rate: crate::SampleRate) -> std::time::Dura
     std::time::Duration::new(secs, nanos)
 }
 
-impl Stream {
+impl PcmStream {
     fn new_input<D, E>(
         inner: Arc<StreamInner>,
         mut data_callback: D,
-        mut error_callback: E,
+        error_callback: E,
         timeout: Option<Duration>,
-    ) -> Stream
+    ) -> PcmStream
     where
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
         let (tx, rx) = trigger();
+        let (mut error_sender, errors) = error_queue("cpal_alsa_in_errors", error_callback);
         // Clone the handle for passing into worker thread.
         let stream = inner.clone();
         let thread = thread::Builder::new()
             .name("cpal_alsa_in".to_owned())
             .spawn(move || {
-                input_stream_worker(
-                    rx,
-                    &stream,
-                    &mut data_callback,
-                    &mut error_callback,
-                    timeout,
-                );
+                input_stream_worker(rx, &stream, &mut data_callback, &mut error_sender, timeout);
             })
             .unwrap();
-        Stream {
+        PcmStream {
             thread: Some(thread),
             inner,
+            capture: None,
             trigger: tx,
+            errors,
         }
     }
 
     fn new_output<D, E>(
         inner: Arc<StreamInner>,
         mut data_callback: D,
-        mut error_callback: E,
+        error_callback: E,
         timeout: Option<Duration>,
-    ) -> Stream
+    ) -> PcmStream
     where
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
         let (tx, rx) = trigger();
+        let (mut error_sender, errors) = error_queue("cpal_alsa_out_errors", error_callback);
         // Clone the handle for passing into worker thread.
         let stream = inner.clone();
         let thread = thread::Builder::new()
             .name("cpal_alsa_out".to_owned())
             .spawn(move || {
-                output_stream_worker(
+                output_stream_worker(rx, &stream, &mut data_callback, &mut error_sender, timeout);
+            })
+            .unwrap();
+        PcmStream {
+            thread: Some(thread),
+            inner,
+            capture: None,
+            trigger: tx,
+            errors,
+        }
+    }
+
+    fn new_duplex<D, E>(
+        capture: Arc<StreamInner>,
+        playback: Arc<StreamInner>,
+        linked: bool,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> PcmStream
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let (tx, rx) = trigger();
+        let (mut error_sender, errors) = error_queue("cpal_alsa_duplex_errors", error_callback);
+        // Clone the handles for passing into worker thread.
+        let channels = (capture.clone(), playback.clone());
+        let thread = thread::Builder::new()
+            .name("cpal_alsa_duplex".to_owned())
+            .spawn(move || {
+                let (capture, playback) = channels;
+                let channels = DuplexChannels {
+                    capture: &capture,
+                    playback: &playback,
+                    linked,
+                };
+                duplex_stream_worker(
                     rx,
-                    &stream,
+                    &channels,
                     &mut data_callback,
-                    &mut error_callback,
+                    &mut error_sender,
                     timeout,
                 );
             })
             .unwrap();
-        Stream {
+        PcmStream {
             thread: Some(thread),
-            inner,
+            inner: playback,
+            capture: Some(capture),
             trigger: tx,
+            errors,
+        }
+    }
+}
+
+impl From<PcmStream> for Stream {
+    fn from(stream: PcmStream) -> Self {
+        Stream(StreamKind::Pcm(stream))
+    }
+}
+
+impl Stream {
+    fn kind(&self) -> &dyn StreamTrait {
+        match &self.0 {
+            StreamKind::Pcm(stream) => stream,
+            StreamKind::Mixed(stream) => stream,
+        }
+    }
+
+    /// Stop passing errors to the error callback, and queue them to be retrieved with
+    /// [`try_recv_error`](Self::try_recv_error) instead.
+    pub fn poll_errors(&self) {
+        match &self.0 {
+            StreamKind::Pcm(stream) => stream.errors.poll(),
+            StreamKind::Mixed(stream) => stream.errors.poll(),
+        }
+    }
+
+    /// The oldest error the stream raised since [`poll_errors`](Self::poll_errors) was called, if
+    /// any.
+    ///
+    /// Only a limited number of errors is queued, and further errors are dropped until they are
+    /// retrieved.
+    pub fn try_recv_error(&self) -> Option<StreamError> {
+        match &self.0 {
+            StreamKind::Pcm(stream) => stream.errors.try_recv(),
+            StreamKind::Mixed(stream) => stream.errors.try_recv(),
         }
     }
 }
 
-impl Drop for Stream {
+impl StreamTrait for Stream {
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.kind().play()
+    }
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.kind().pause()
+    }
+    fn can_pause(&self) -> bool {
+        self.kind().can_pause()
+    }
+    fn stats(&self) -> Option<StreamStats> {
+        self.kind().stats()
+    }
+    fn latency(&self) -> Option<Duration> {
+        self.kind().latency()
+    }
+    fn negotiated_config(&self) -> Option<NegotiatedStreamConfig> {
+        self.kind().negotiated_config()
+    }
+}
+
+impl Drop for PcmStream {
     fn drop(&mut self) {
         self.inner.dropping.set(true);
+        // Duplex workers poll the capture channel, which must see the request too, as the wakeup
+        // is only read once.
+        if let Some(capture) = &self.capture {
+            capture.dropping.set(true);
+        }
         self.trigger.wakeup();
         self.thread.take().unwrap().join().unwrap();
     }
 }
 
-impl StreamTrait for Stream {
+impl StreamTrait for PcmStream {
     fn play(&self) -> Result<(), PlayStreamError> {
-        self.inner.channel.pause(false).ok();
+        self.inner.paused.store(false, Ordering::Release);
+        self.trigger.wakeup();
         Ok(())
     }
     fn pause(&self) -> Result<(), PauseStreamError> {
-        self.inner.channel.pause(true).ok();
+        self.inner.paused.store(true, Ordering::Release);
+        self.trigger.wakeup();
         Ok(())
     }
+    fn can_pause(&self) -> bool {
+        // Duplex streams are stopped when paused, see `duplex_stream_worker`.
+        self.capture.is_none() && self.inner.can_pause
+    }
+    fn stats(&self) -> Option<StreamStats> {
+        Some(self.inner.stats.snapshot())
+    }
+    fn latency(&self) -> Option<Duration> {
+        // `snd_pcm_delay` covers the frames buffered for the device and its own delay, and fails
+        // while the stream is in an xrun.
+        let mut delay = self.inner.channel.delay().ok()?.max(0) as usize;
+        if let Some(capture) = &self.capture {
+            delay += capture.channel.delay().ok()?.max(0) as usize;
+        }
+        let period = self.inner.buffer_layout.period_size as usize;
+        Some(frames_to_duration(
+            delay + period,
+            self.inner.conf.sample_rate,
+        ))
+    }
+    fn negotiated_config(&self) -> Option<NegotiatedStreamConfig> {
+        // The channel count and rate are set exactly, while ALSA may adjust the buffer layout.
+        Some(NegotiatedStreamConfig {
+            config: StreamConfig {
+                channels: self.inner.conf.channels,
+                sample_rate: self.inner.conf.sample_rate,
+                buffer_size: BufferSize::Fixed(self.inner.buffer_layout.buffer_size),
+            },
+            input_channels: self.capture.as_ref().map(|capture| capture.conf.channels),
+            sample_format: self.inner.sample_format,
+            period_size: self.inner.buffer_layout.period_size,
+        })
+    }
+}
+
+// Discover every value between `min` and `max` of a hardware parameter, such as the rate, that
+// only takes discrete values.
+//
+// `nearest` refines a copy of the device's `HwParams` to the supported value nearest to the
+// requested one, which never decreases as the requested value grows. The value following a
+// supported value is therefore found by bisecting for the smallest request that is refined to a
+// greater value.
+fn discrete_values<E>(
+    nearest: impl Fn(u32) -> Result<u32, E>,
+    min: u32,
+    max: u32,
+) -> Result<Vec<u32>, E> {
+    let mut values = vec![min];
+    let mut value = min;
+    while value < max {
+        // `low` is always refined to `value` and `high` to a greater value.
+        let (mut low, mut high) = (value, max);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if nearest(mid)? > value {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        value = nearest(high)?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+// Merge sorted values into runs of consecutive values.
+fn consecutive_runs(values: &[u32]) -> Vec<(u32, u32)> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &value in values {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == value => *end = value,
+            _ => runs.push((value, value)),
+        }
+    }
+    runs
 }
 
 fn set_hw_params_from_format(
     pcm_handle: &alsa::pcm::PCM,
     config: &StreamConfig,
     sample_format: SampleFormat,
-) -> Result<bool, BackendSpecificError> {
+    params: &BufferParams,
+) -> Result<(alsa::pcm::Format, bool), BackendSpecificError> {
     let hw_params = alsa::pcm::HwParams::any(pcm_handle)?;
     hw_params.set_access(alsa::pcm::Access::RWInterleaved)?;
 
-    let sample_format = if cfg!(target_endian = "big") {
-        match sample_format {
-            SampleFormat::I8 => alsa::pcm::Format::S8,
-            SampleFormat::I16 => alsa::pcm::Format::S16BE,
-            // SampleFormat::I24 => alsa::pcm::Format::S24BE,
-            SampleFormat::I24 => alsa::pcm::Format::S243LE,
-            SampleFormat::I32 => alsa::pcm::Format::S32BE,
-            // SampleFormat::I48 => alsa::pcm::Format::S48BE,
-            // SampleFormat::I64 => alsa::pcm::Format::S64BE,
-            SampleFormat::U8 => alsa::pcm::Format::U8,
-            SampleFormat::U16 => alsa::pcm::Format::U16BE,
-            // SampleFormat::U24 => alsa::pcm::Format::U24BE,
-            SampleFormat::U32 => alsa::pcm::Format::U32BE,
-            // SampleFormat::U48 => alsa::pcm::Format::U48BE,
-            // SampleFormat::U64 => alsa::pcm::Format::U64BE,
-            SampleFormat::F32 => alsa::pcm::Format::FloatBE,
-            SampleFormat::F64 => alsa::pcm::Format::Float64BE,
-            sample_format => {
-                return Err(BackendSpecificError {
-                    description: format!(
-                        "Sample format '{}' is not supported by this backend",
-                        sample_format
-                    ),
-                })
-            }
-        }
-    } else {
-        match sample_format {
-            SampleFormat::I8 => alsa::pcm::Format::S8,
-            SampleFormat::I16 => alsa::pcm::Format::S16LE,
-            SampleFormat::I24 => alsa::pcm::Format::S243LE,
-            SampleFormat::I32 => alsa::pcm::Format::S32LE,
-            // SampleFormat::I48 => alsa::pcm::Format::S48LE,
-            // SampleFormat::I64 => alsa::pcm::Format::S64LE,
-            SampleFormat::U8 => alsa::pcm::Format::U8,
-            SampleFormat::U16 => alsa::pcm::Format::U16LE,
-            // SampleFormat::U24 => alsa::pcm::Format::U24LE,
-            SampleFormat::U32 => alsa::pcm::Format::U32LE,
-            // SampleFormat::U48 => alsa::pcm::Format::U48LE,
-            // SampleFormat::U64 => alsa::pcm::Format::U64LE,
-            SampleFormat::F32 => alsa::pcm::Format::FloatLE,
-            SampleFormat::F64 => alsa::pcm::Format::Float64LE,
-            sample_format => {
-                return Err(BackendSpecificError {
-                    description: format!(
-                        "Sample format '{}' is not supported by this backend",
-                        sample_format
-                    ),
-                })
-            }
-        }
-    };
-
-    hw_params.set_format(sample_format)?;
+    // Prefer an ALSA format exposed as `sample_format` that the device supports without conversion,
+    // then any other supported one. If none are supported, fall back to the first so that
+    // `set_format` reports the error.
+    let candidates = FORMATS
+        .iter()
+        .filter(|&&(format, _)| format == sample_format)
+        .map(|&(_, alsa_format)| alsa_format);
+    let supported = |f: &alsa::pcm::Format| hw_params.test_format(*f).is_ok();
+    let alsa_format = candidates
+        .clone()
+        .filter(supported)
+        .find(|&f| Codec::new(f, sample_format).is_none())
+        .or_else(|| candidates.clone().find(supported))
+        .or_else(|| candidates.clone().next())
+        .ok_or_else(|| BackendSpecificError {
+            description: format!(
+                "Sample format '{}' is not supported by this backend",
+                sample_format
+            ),
+        })?;
+
+    hw_params.set_format(alsa_format)?;
     hw_params.set_rate(config.sample_rate.0, alsa::ValueOr::Nearest)?;
     hw_params.set_channels(config.channels as u32)?;
 
-    match config.buffer_size {
-        BufferSize::Fixed(v) => {
-            hw_params.set_period_size_near((v / 4) as alsa::pcm::Frames, alsa::ValueOr::Nearest)?;
-            hw_params.set_buffer_size(v as alsa::pcm::Frames)?;
+    // Explicit buffer parameters take precedence, and the buffer size of the config only decides
+    // the ones left out. The default values together represent a moderate latency and wakeup
+    // interval. Without them, we are at the mercy of the device.
+    match (params.period_size, config.buffer_size) {
+        (Some(frames), _) => {
+            hw_params.set_period_size_near(frames as alsa::pcm::Frames, alsa::ValueOr::Nearest)?;
         }
-        BufferSize::Default => {
-            // These values together represent a moderate latency and wakeup interval.
-            // Without them, we are at the mercy of the device
+        (None, BufferSize::Fixed(v)) => {
+            let frames = v / params.periods.unwrap_or(4);
+            hw_params.set_period_size_near(frames as alsa::pcm::Frames, alsa::ValueOr::Nearest)?;
+        }
+        (None, BufferSize::Default) => {
             hw_params.set_period_time_near(25_000, alsa::ValueOr::Nearest)?;
+        }
+    }
+    match (params.periods, config.buffer_size) {
+        (Some(periods), _) => hw_params.set_periods(periods, alsa::ValueOr::Nearest)?,
+        (None, BufferSize::Fixed(v)) => hw_params.set_buffer_size(v as alsa::pcm::Frames)?,
+        (None, BufferSize::Default) => {
             hw_params.set_buffer_time_near(100_000, alsa::ValueOr::Nearest)?;
         }
     }
 
     pcm_handle.hw_params(&hw_params)?;
 
-    Ok(hw_params.can_pause())
+    Ok((alsa_format, hw_params.can_pause()))
 }
 
+// Returns the negotiated buffer layout.
 fn set_sw_params_from_format(
     pcm_handle: &alsa::pcm::PCM,
-    config: &StreamConfig,
     stream_type: alsa::Direction,
-) -> Result<usize, BackendSpecificError> {
+    params: &BufferParams,
+) -> Result<BufferLayout, BackendSpecificError> {
     let sw_params = pcm_handle.sw_params_current()?;
 
-    let period_len = {
+    {
         let (buffer, period) = pcm_handle.get_params()?;
         if buffer == 0 {
             return Err(BackendSpecificError {
                 description: "initialization resulted in a null buffer".to_string(),
             });
         }
-        sw_params.set_avail_min(period as alsa::pcm::Frames)?;
+        let avail_min = params.avail_min.map_or(period, |frames| frames as u64);
+        sw_params.set_avail_min(avail_min as alsa::pcm::Frames)?;
 
-        let start_threshold = match stream_type {
-            alsa::Direction::Playback => buffer - period,
+        let start_threshold = match (stream_type, params.start_threshold) {
+            (alsa::Direction::Playback, Some(frames)) => frames as u64,
+            (alsa::Direction::Playback, None) => buffer - period,
 
             // For capture streams, the start threshold is irrelevant and ignored,
-            // because build_stream_inner() starts the stream before process_input()
+            // because build_input_stream_raw() starts the stream before process_input()
             // reads from it. Set it anyway I guess, since it's better than leaving
             // it at an unspecified default value.
-            alsa::Direction::Capture => 1,
+            (alsa::Direction::Capture, _) => 1,
         };
         sw_params.set_start_threshold(start_threshold.try_into().unwrap())?;
-
-        period as usize * config.channels as usize
-    };
+    }
 
     sw_params.set_tstamp_mode(true)?;
     sw_params.set_tstamp_type(alsa::pcm::TstampType::MonotonicRaw)?;
@@ -1226,7 +1848,64 @@ fn set_sw_params_from_format(
         pcm_handle.sw_params(&sw_params)?;
     }
 
-    Ok(period_len)
+    let (buffer_size, period_size) = pcm_handle.get_params()?;
+    let sw_params = pcm_handle.sw_params_current()?;
+    Ok(BufferLayout {
+        periods: pcm_handle.hw_params_current()?.get_periods()?,
+        period_size: period_size as FrameCount,
+        buffer_size: buffer_size as FrameCount,
+        start_threshold: sw_params.get_start_threshold()? as FrameCount,
+        avail_min: sw_params.get_avail_min()? as FrameCount,
+    })
+}
+
+// An error raised on a stream's worker thread.
+//
+// Describing an error allocates, so this is only turned into a `StreamError` on the thread that
+// passes errors on to the user.
+enum WorkerError {
+    Alsa(alsa::Error),
+    // Only variants that don't allocate are sent this way.
+    Stream(StreamError),
+    SpuriousPoll,
+    UnexpectedFrameCount {
+        expected: usize,
+        result: usize,
+    },
+    TimestampBeforeTrigger {
+        ts: libc::timespec,
+        trigger_ts: libc::timespec,
+    },
+    TimestampOverflow,
+}
+
+impl From<alsa::Error> for WorkerError {
+    fn from(err: alsa::Error) -> Self {
+        WorkerError::Alsa(err)
+    }
+}
+
+impl From<WorkerError> for StreamError {
+    fn from(err: WorkerError) -> Self {
+        let description = match err {
+            WorkerError::Alsa(err) => return err.into(),
+            WorkerError::Stream(err) => return err,
+            WorkerError::SpuriousPoll => String::from("`alsa::poll()` spuriously returned"),
+            WorkerError::UnexpectedFrameCount { expected, result } => format!(
+                "unexpected number of frames written: expected {}, \
+                 result {} (this should never happen)",
+                expected, result,
+            ),
+            WorkerError::TimestampBeforeTrigger { ts, trigger_ts } => format!(
+                "get_htstamp `{}.{}` was earlier than get_trigger_htstamp `{}.{}`",
+                ts.tv_sec, ts.tv_nsec, trigger_ts.tv_sec, trigger_ts.tv_nsec
+            ),
+            WorkerError::TimestampOverflow => {
+                String::from("stream duration has exceeded `StreamInstant` representation")
+            }
+        };
+        BackendSpecificError { description }.into()
+    }
 }
 
 impl From<alsa::Error> for BackendSpecificError {
@@ -1271,3 +1950,236 @@ impl From<alsa::Error> for StreamError {
         err.into()
     }
 }
+
+#[test]
+fn test_discrete_values() {
+    let supported: [u32; 9] = [
+        8000, 11025, 24000, 44100, 48000, 352800, 384000, 705600, 768000,
+    ];
+    let nearest = |rate: u32| -> Result<u32, ()> {
+        Ok(*supported
+            .iter()
+            .min_by_key(|&&supported| supported.abs_diff(rate))
+            .unwrap())
+    };
+    assert_eq!(
+        discrete_values(nearest, 8000, 768000),
+        Ok(supported.to_vec())
+    );
+    assert_eq!(
+        consecutive_runs(&[1, 2, 3, 8, 10, 11]),
+        [(1, 3), (8, 8), (10, 11)]
+    );
+}
+
+// The "null" PCM, which takes any config and discards or generates silence.
+#[cfg(test)]
+fn null_device() -> Device {
+    Device {
+        name: "null".to_string(),
+        pcm_id: "null".to_string(),
+        description: None,
+        direction: PcmDirection::Both,
+        buffer_params: Default::default(),
+    }
+}
+
+// Wait for ten more callbacks to be counted by `callbacks`.
+#[cfg(test)]
+fn wait_for_callbacks(callbacks: &std::sync::atomic::AtomicUsize) {
+    let start = callbacks.load(Ordering::SeqCst);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while callbacks.load(Ordering::SeqCst) < start + 10 {
+        assert!(Instant::now() < deadline, "the stream is stuck");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn test_pause_and_play() {
+    let device = null_device();
+    let config = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Fixed(256),
+    };
+    let callbacks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = callbacks.clone();
+    let stream = device
+        .build_output_stream(
+            &config,
+            move |data: &mut [i16], _: &OutputCallbackInfo| {
+                data.fill(0);
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+            |err| panic!("{}", err),
+            None,
+        )
+        .unwrap();
+
+    stream.play().unwrap();
+    wait_for_callbacks(&callbacks);
+
+    stream.pause().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let paused = callbacks.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(callbacks.load(Ordering::SeqCst), paused);
+    let stats = stream.stats().unwrap();
+    assert_eq!(stats.callbacks, paused as u64);
+    assert!(stats.frames >= stats.callbacks);
+    assert!(stats.min_callback_duration <= stats.max_callback_duration);
+    assert!(!stats.period.is_zero());
+
+    stream.play().unwrap();
+    wait_for_callbacks(&callbacks);
+    assert!(stream.latency().unwrap() >= stats.period);
+}
+
+#[test]
+fn test_duplex_stream() {
+    let device = null_device();
+    let config = DuplexStreamConfig {
+        input_channels: 1,
+        output_channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Fixed(256),
+    };
+    let callbacks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = callbacks.clone();
+    let stream = device
+        .build_duplex_stream(
+            &config,
+            move |input: &[i16], output: &mut [i16], info: &DuplexCallbackInfo| {
+                assert_eq!(input.len() * 2, output.len());
+                let timestamp = info.timestamp();
+                assert!(timestamp.capture <= timestamp.callback);
+                assert!(timestamp.callback <= timestamp.playback);
+                output.fill(0);
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+            |err| panic!("{}", err),
+            None,
+        )
+        .unwrap();
+    assert!(!stream.can_pause());
+    let negotiated = stream.negotiated_config().unwrap();
+    assert_eq!(negotiated.config.channels, 2);
+    assert_eq!(negotiated.input_channels, Some(1));
+    wait_for_callbacks(&callbacks);
+
+    stream.pause().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let paused = callbacks.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(callbacks.load(Ordering::SeqCst), paused);
+    assert_eq!(stream.stats().unwrap().callbacks, paused as u64);
+
+    stream.play().unwrap();
+    wait_for_callbacks(&callbacks);
+}
+
+#[test]
+fn test_buffer_params() {
+    let mut device = null_device();
+    device.set_buffer_params(BufferParams {
+        periods: Some(3),
+        period_size: Some(128),
+        start_threshold: Some(256),
+        avail_min: Some(256),
+    });
+    let config = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Default,
+    };
+    let stream = device
+        .build_output_stream(
+            &config,
+            |data: &mut [i16], _: &OutputCallbackInfo| data.fill(0),
+            |err| panic!("{}", err),
+            None,
+        )
+        .unwrap();
+    let negotiated = stream.negotiated_config().unwrap();
+    assert_eq!(negotiated.config.buffer_size, BufferSize::Fixed(384));
+    assert_eq!(negotiated.period_size, 128);
+    assert_eq!(
+        stream.stats().unwrap().period,
+        frames_to_duration(128, config.sample_rate)
+    );
+    assert_eq!(
+        stream.buffer_layout(),
+        BufferLayout {
+            periods: 3,
+            period_size: 128,
+            buffer_size: 384,
+            start_threshold: 256,
+            avail_min: 256,
+        }
+    );
+}
+
+#[test]
+fn test_zero_buffer_params() {
+    let config = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Fixed(512),
+    };
+    for params in [
+        BufferParams {
+            periods: Some(0),
+            ..Default::default()
+        },
+        BufferParams {
+            period_size: Some(0),
+            ..Default::default()
+        },
+    ] {
+        let mut device = null_device();
+        device.set_buffer_params(params);
+        let result = device.build_output_stream(
+            &config,
+            |data: &mut [i16], _: &OutputCallbackInfo| data.fill(0),
+            |err| panic!("{}", err),
+            None,
+        );
+        assert!(matches!(result, Err(BuildStreamError::InvalidArgument)));
+    }
+}
+
+#[test]
+fn test_device_by_id() {
+    let host = Host::new().unwrap();
+    let id: DeviceId = "null".parse().unwrap();
+    let device = host.device_by_id(&id).unwrap().unwrap();
+    assert_eq!(DeviceTrait::id(&device), Ok(id));
+    let missing: DeviceId = "hw:CARD=cpal_missing".parse().unwrap();
+    assert!(host.device_by_id(&missing).unwrap().is_none());
+}
+
+#[test]
+fn test_enumerate_hints() {
+    // The "null" PCM isn't a builtin, but is defined with a hint in ALSA's configuration.
+    let null = Devices::new()
+        .unwrap()
+        .find(|device| device.pcm_id == "null")
+        .unwrap();
+    assert_eq!(null.name, "null");
+    assert!(null.description().is_some());
+    assert_eq!(null.direction(), PcmDirection::Both);
+}
+
+#[test]
+fn test_enumerate_does_not_open() {
+    // Listing output devices and querying their configs leaves their PCMs free for other clients.
+    let devices: Vec<_> = Host::new().unwrap().output_devices().unwrap().collect();
+    assert!(devices.iter().any(|device| device.pcm_id == "null"));
+    for device in devices {
+        drop(device.supported_output_configs());
+        if let Err(err) = alsa::PCM::new(&device.pcm_id, alsa::Direction::Playback, true) {
+            assert_ne!(err.errno(), libc::EBUSY, "{} is kept open", device.pcm_id);
+        }
+    }
+}
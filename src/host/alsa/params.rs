@@ -0,0 +1,69 @@
+//! Control over the layout of the ALSA ring buffer behind a stream.
+
+use super::{Device, Stream, StreamKind};
+use crate::FrameCount;
+
+/// The layout of the ring buffer to request for the streams of a [`Device`], see
+/// [`Device::set_buffer_params`].
+///
+/// Fields left as `None` are derived from the [`BufferSize`](crate::BufferSize) of the stream's
+/// config, as they are without these parameters. ALSA picks the supported values nearest to the
+/// requested ones, which are reported by [`Stream::buffer_layout`]. Building a stream with a
+/// period count or size of zero fails with [`BuildStreamError::InvalidArgument`].
+///
+/// [`BuildStreamError::InvalidArgument`]: crate::BuildStreamError::InvalidArgument
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BufferParams {
+    /// The number of periods in the ring buffer, usually 2 or 3.
+    pub periods: Option<u32>,
+    /// The number of frames in a period, i.e. between two interrupts of the device.
+    pub period_size: Option<FrameCount>,
+    /// The number of frames that have to be written before a playback stream starts. Defaults to
+    /// the buffer size minus one period.
+    pub start_threshold: Option<FrameCount>,
+    /// The number of frames that have to be available before the stream's data callback is
+    /// called. Defaults to one period, which is also the minimum ALSA accepts.
+    pub avail_min: Option<FrameCount>,
+}
+
+/// The layout of the ring buffer that ALSA negotiated for a stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferLayout {
+    /// The number of periods in the ring buffer.
+    pub periods: u32,
+    /// The number of frames in a period.
+    pub period_size: FrameCount,
+    /// The number of frames in the ring buffer.
+    pub buffer_size: FrameCount,
+    /// The number of frames that have to be written before a playback stream starts.
+    pub start_threshold: FrameCount,
+    /// The number of frames that have to be available before the stream's data callback is
+    /// called.
+    pub avail_min: FrameCount,
+}
+
+impl Device {
+    /// The buffer layout requested for streams built from this device.
+    pub fn buffer_params(&self) -> BufferParams {
+        self.buffer_params
+    }
+
+    /// Request a buffer layout for streams built from this device from now on, including duplex
+    /// streams.
+    pub fn set_buffer_params(&mut self, params: BufferParams) {
+        self.buffer_params = params;
+    }
+}
+
+impl Stream {
+    /// The layout of the ring buffer that ALSA negotiated for this stream.
+    ///
+    /// For duplex streams, this is the layout of the playback side, and for output streams added to
+    /// the mixer of shared streams, that of the mixer.
+    pub fn buffer_layout(&self) -> BufferLayout {
+        match &self.0 {
+            StreamKind::Pcm(stream) => stream.inner.buffer_layout,
+            StreamKind::Mixed(stream) => stream.buffer_layout(),
+        }
+    }
+}
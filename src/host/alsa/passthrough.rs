@@ -0,0 +1,127 @@
+//! Passthrough of IEC 61937 bitstreams to S/PDIF and HDMI receivers.
+
+use super::alsa;
+use super::enumerate::card_of;
+use super::{Device, PcmDirection, PcmStream, Stream};
+use crate::iec61937::ChannelStatus;
+use crate::{
+    BufferSize, BuildStreamError, Data, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig,
+    StreamError,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The digital output of a card that leads to the receiver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PassthroughOutput {
+    /// The S/PDIF output, through ALSA's `iec958` PCM.
+    Iec958,
+    /// The HDMI output with the given index, through ALSA's `hdmi` PCM.
+    Hdmi { device: u32 },
+}
+
+/// The configuration of a stream built with [`Device::build_passthrough_stream`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassthroughConfig {
+    /// The digital output of the device's card to send the stream to.
+    pub output: PassthroughOutput,
+    /// The sample rate of the IEC 61937 stream, which depends on the data type (e.g. four times
+    /// the rate of the encoded audio for E-AC-3).
+    pub sample_rate: SampleRate,
+    /// The buffer size of the stream, in frames of two `i16` samples.
+    pub buffer_size: BufferSize,
+    /// The channel status sent to the receiver, usually [`ChannelStatus::non_audio`].
+    pub channel_status: ChannelStatus,
+}
+
+impl Device {
+    /// Build an output stream that sends an IEC 61937 bitstream, such as AC-3 or DTS, to the
+    /// receiver untouched.
+    ///
+    /// The stream is opened on the digital output of this device's card, with the channel status
+    /// bits from `config`. It always has two channels of `i16` samples, which the callback fills
+    /// with bursts built by [`write_burst`](crate::iec61937::write_burst).
+    pub fn build_passthrough_stream<D, E>(
+        &self,
+        config: &PassthroughConfig,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        D: FnMut(&mut [i16], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let device = Device {
+            name: self.name.clone(),
+            pcm_id: passthrough_pcm_id(&self.pcm_id, config),
+            description: None,
+            direction: PcmDirection::Playback,
+            buffer_params: self.buffer_params,
+        };
+        let conf = StreamConfig {
+            channels: 2,
+            sample_rate: config.sample_rate,
+            buffer_size: config.buffer_size,
+        };
+        let stream_inner =
+            device.build_stream_inner(&conf, SampleFormat::I16, alsa::Direction::Playback)?;
+        let stream = PcmStream::new_output(
+            Arc::new(stream_inner),
+            move |data: &mut Data, info: &OutputCallbackInfo| {
+                let samples = data
+                    .as_slice_mut()
+                    .expect("passthrough streams always use `SampleFormat::I16`");
+                data_callback(samples, info)
+            },
+            error_callback,
+            timeout,
+        );
+        Ok(stream.into())
+    }
+}
+
+// The id of the PCM that passes data through to `config.output` on the card of `pcm_id`.
+//
+// The channel status is set by the `iec958` and `hdmi` PCMs through their `AES0` to `AES3`
+// arguments. Without a card in `pcm_id`, the default card is used.
+fn passthrough_pcm_id(pcm_id: &str, config: &PassthroughConfig) -> String {
+    let mut args = Vec::new();
+    if let Some(card) = card_of(pcm_id) {
+        args.push(format!("CARD={}", card));
+    }
+    let plugin = match config.output {
+        PassthroughOutput::Iec958 => "iec958",
+        PassthroughOutput::Hdmi { device } => {
+            args.push(format!("DEV={}", device));
+            "hdmi"
+        }
+    };
+    for (i, byte) in config.channel_status.0.iter().enumerate() {
+        args.push(format!("AES{}=0x{:02x}", i, byte));
+    }
+    format!("{}:{}", plugin, args.join(","))
+}
+
+#[test]
+fn test_passthrough_pcm_id() {
+    let mut config = PassthroughConfig {
+        output: PassthroughOutput::Iec958,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Default,
+        channel_status: ChannelStatus::non_audio(SampleRate(48_000)),
+    };
+    assert_eq!(
+        passthrough_pcm_id("hw:1", &config),
+        "iec958:CARD=1,AES0=0x06,AES1=0x82,AES2=0x00,AES3=0x02"
+    );
+    config.output = PassthroughOutput::Hdmi { device: 2 };
+    assert_eq!(
+        passthrough_pcm_id("plughw:CARD=PCH,DEV=0", &config),
+        "hdmi:CARD=PCH,DEV=2,AES0=0x06,AES1=0x82,AES2=0x00,AES3=0x02"
+    );
+    assert_eq!(
+        passthrough_pcm_id("default", &config),
+        "hdmi:DEV=2,AES0=0x06,AES1=0x82,AES2=0x00,AES3=0x02"
+    );
+}
@@ -0,0 +1,815 @@
+//! Output streams sharing a PCM, mixed in-process.
+//!
+//! PCMs that drive the hardware directly, such as `hw` and `plughw`, only accept one stream at a
+//! time. Streams built with [`Device::build_shared_output_stream`] are instead added to a mixer
+//! that owns the only stream of their PCM. It sums what each of them writes, and closes the PCM
+//! once the last of them is dropped. The `hw` and `plughw` PCMs of the same card device share a
+//! mixer, as they open the same hardware. Output streams built on a PCM that a mixer holds
+//! exclusively are added to the mixer as well.
+//!
+//! The worker thread of a mixer owns its sources, and is sent the ones added or removed through a
+//! channel, so that it never waits for a lock. Removed sources are sent back to the thread that
+//! removed them, which waits for them so that they are dropped along with their stream, unless it
+//! is a thread of the mixer itself. No lock is held while user code runs or a mixer is closed, so
+//! callbacks may build and drop shared streams themselves.
+
+use super::enumerate::card_of;
+use super::{alsa, BufferLayout, Device, PcmStream, Stream, StreamKind};
+use crate::host::error_queue::{error_queue, ErrorReceiver};
+use crate::traits::StreamTrait;
+use crate::{
+    ALaw, BuildStreamError, Data, FromSample, MuLaw, NegotiatedStreamConfig, OutputCallbackInfo,
+    PauseStreamError, PlayStreamError, SampleFormat, SizedSample, StreamConfig, StreamError,
+    StreamStats, SupportedStreamConfigRange, SupportedStreamConfigsError, I24, I48, U24, U48,
+};
+use std::cell::Cell;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// Whether the PCM of a device accepts several streams at once, see [`Device::sharing`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sharing {
+    /// The PCM mixes the streams opened on it by itself, as ALSA's `dmix` plugin and sound
+    /// servers do.
+    Shared,
+    /// The PCM accepts a single stream at a time, and is busy for any other. Streams built with
+    /// [`Device::build_shared_output_stream`] can still share it, as they are mixed by cpal.
+    Exclusive,
+}
+
+// The formats the mixer writes to its PCM, in order of preference. The streams are mixed as `f32`.
+const MIX_FORMATS: [SampleFormat; 4] = [
+    SampleFormat::F32,
+    SampleFormat::I32,
+    SampleFormat::I24,
+    SampleFormat::I16,
+];
+
+// The mixers of every PCM shared so far.
+static MIXERS: Mutex<Mixers> = Mutex::new(Mixers {
+    open: Vec::new(),
+    opening: Vec::new(),
+    closing: Vec::new(),
+});
+
+// Notified whenever a mixer has been opened or closed, or failed to open.
+static SETTLED: Condvar = Condvar::new();
+
+// Identifies the streams and mixers.
+static NEXT_STREAM_ID: AtomicU64 = AtomicU64::new(0);
+static NEXT_MIXER_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // The mixer whose data or error callbacks run on this thread, if any.
+    static MIXER_THREAD: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+struct Mixers {
+    open: Vec<Arc<Mixer>>,
+    // The keys of the mixers whose PCM is being opened, which `MIXERS` isn't locked for. Streams
+    // for the same key wait for them.
+    opening: Vec<String>,
+    // The keys of the mixers being closed, which still hold their PCM. A new mixer for the same
+    // key waits for it to be closed.
+    closing: Vec<String>,
+}
+
+type DataCallback = Box<dyn FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static>;
+type ErrorCallback = Arc<Mutex<dyn FnMut(StreamError) + Send + 'static>>;
+
+// A stream added to a mixer.
+struct Source {
+    id: u64,
+    paused: Arc<AtomicBool>,
+    data_callback: DataCallback,
+    // What the stream wrote for the current period, before it is added to the mix.
+    buffer: Vec<f32>,
+}
+
+// A change to the sources of a mixer, sent to its worker thread.
+enum Command {
+    Add(Source),
+    // Remove the source with the given id, and send it back through the sender.
+    Remove(u64, Sender<Source>),
+}
+
+// The sources of a mixer, owned by its worker thread.
+struct Sources {
+    list: Vec<Source>,
+    commands: Receiver<Command>,
+}
+
+impl Sources {
+    // Apply the commands sent since the last period, without blocking.
+    fn update(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            match command {
+                Command::Add(source) => self.list.push(source),
+                Command::Remove(id, retired) => {
+                    // Sources that aren't waited for are dropped here.
+                    if let Some(index) = self.list.iter().position(|source| source.id == id) {
+                        let _ = retired.send(self.list.swap_remove(index));
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Mixer {
+    // Tells the threads of the mixer apart, see `MIXER_THREAD`.
+    id: u64,
+    // The card device of the PCM, see `mixer_key`.
+    key: String,
+    // The channels and rate of the PCM, which every stream of the mixer has to use.
+    config: StreamConfig,
+    // The number of samples in the ring buffer of the PCM, which no period exceeds.
+    buffer_samples: usize,
+    // The number of streams of the mixer, only changed while `MIXERS` is locked.
+    streams: AtomicUsize,
+    // Sends the sources added and removed to the worker thread of `stream`.
+    commands: Mutex<Sender<Command>>,
+    // The error callbacks of the streams, which each get the errors of the PCM.
+    error_callbacks: Arc<Mutex<Vec<(u64, ErrorCallback)>>>,
+    stream: PcmStream,
+}
+
+impl Mixer {
+    fn open(device: &Device, config: &StreamConfig) -> Result<Self, BuildStreamError> {
+        let sample_format = mix_format(device, config)?;
+        let inner = device.build_stream_inner(config, sample_format, alsa::Direction::Playback)?;
+        let buffer_samples = inner.buffer_layout.buffer_size as usize * config.channels as usize;
+
+        let id = NEXT_MIXER_ID.fetch_add(1, Ordering::Relaxed);
+        let (commands, command_receiver) = mpsc::channel();
+        let mut sources = Sources {
+            list: Vec::new(),
+            commands: command_receiver,
+        };
+        let error_callbacks: Arc<Mutex<Vec<(u64, ErrorCallback)>>> = Default::default();
+        let mut mix = Vec::with_capacity(buffer_samples);
+        let data_callback = move |data: &mut Data, info: &OutputCallbackInfo| {
+            MIXER_THREAD.with(|mixer| mixer.set(Some(id)));
+            sources.update();
+            mix.clear();
+            mix.resize(data.len(), 0f32);
+            for source in sources.list.iter_mut() {
+                if source.paused.load(Ordering::Acquire) {
+                    continue;
+                }
+                source.buffer.clear();
+                source.buffer.resize(data.len(), 0f32);
+                (source.data_callback)(&mut source.buffer, info);
+                for (mixed, sample) in mix.iter_mut().zip(&source.buffer) {
+                    *mixed += sample;
+                }
+            }
+            write_mix(data, &mix);
+        };
+        let stream_error_callbacks = error_callbacks.clone();
+        let error_callback = move |err: StreamError| {
+            MIXER_THREAD.with(|mixer| mixer.set(Some(id)));
+            // The list is only locked to copy it, so that the callbacks can build and drop streams.
+            let error_callbacks: Vec<_> = lock(&stream_error_callbacks)
+                .iter()
+                .map(|(_, error_callback)| error_callback.clone())
+                .collect();
+            for error_callback in error_callbacks {
+                (*lock(&error_callback))(err.clone());
+            }
+        };
+        let stream = PcmStream::new_output(Arc::new(inner), data_callback, error_callback, None);
+
+        Ok(Mixer {
+            id,
+            key: mixer_key(&device.pcm_id),
+            config: config.clone(),
+            buffer_samples,
+            streams: AtomicUsize::new(0),
+            commands: Mutex::new(commands),
+            error_callbacks,
+            stream,
+        })
+    }
+}
+
+// The card device a mixer for `pcm_id` opens, e.g. `hw:0,3,-1` for both `hw:0,3` and
+// `plughw:CARD=PCH,DEV=3` if `PCH` is the first card. A subdevice of `-1` stands for any.
+//
+// PCMs that don't belong to a card are told apart by their id.
+fn mixer_key(pcm_id: &str) -> String {
+    let Some(card) = card_of(pcm_id) else {
+        return pcm_id.to_owned();
+    };
+    // Cards named by their id are looked up, and kept as is if they are missing.
+    let card = CString::new(card)
+        .ok()
+        .and_then(|name| alsa::card::Card::from_str(&name).ok())
+        .map_or_else(|| card.to_owned(), |card| card.get_index().to_string());
+    let (mut device, mut subdevice) = ("0", "-1");
+    let (_, args) = pcm_id.split_once(':').unwrap();
+    for (i, arg) in args.split(',').enumerate().skip(1) {
+        match (i, arg.split_once('=')) {
+            (_, Some(("DEV", value))) => device = value,
+            (_, Some(("SUBDEV", value))) => subdevice = value,
+            (1, None) => device = arg,
+            (2, None) => subdevice = arg,
+            _ => {}
+        }
+    }
+    format!("hw:{},{},{}", card, device, subdevice)
+}
+
+// The first of `MIX_FORMATS` that the device supports with the channels and rate of `config`.
+fn mix_format(device: &Device, config: &StreamConfig) -> Result<SampleFormat, BuildStreamError> {
+    let ranges: Vec<_> = device
+        .supported_output_configs()
+        .map_err(|err| match err {
+            SupportedStreamConfigsError::DeviceNotAvailable => BuildStreamError::DeviceNotAvailable,
+            SupportedStreamConfigsError::InvalidArgument => BuildStreamError::InvalidArgument,
+            SupportedStreamConfigsError::BackendSpecific { err } => err.into(),
+        })?
+        .collect();
+    supported_mix_format(&ranges, config).ok_or(BuildStreamError::StreamConfigNotSupported)
+}
+
+// The first of `MIX_FORMATS` that one of `ranges` supports with the channels and rate of `config`.
+fn supported_mix_format(
+    ranges: &[SupportedStreamConfigRange],
+    config: &StreamConfig,
+) -> Option<SampleFormat> {
+    MIX_FORMATS.into_iter().find(|&format| {
+        ranges.iter().any(|range| {
+            range.sample_format() == format
+                && range.supports_channels(config.channels)
+                && range.min_sample_rate() <= config.sample_rate
+                && config.sample_rate <= range.max_sample_rate()
+        })
+    })
+}
+
+// Write the sum of the streams to `data`, clipping what exceeds the range of the samples.
+fn write_mix(data: &mut Data, mix: &[f32]) {
+    fn write<T: SizedSample + FromSample<f32>>(data: &mut Data, mix: &[f32]) {
+        if let Some(samples) = data.as_slice_mut::<T>() {
+            for (sample, &mixed) in samples.iter_mut().zip(mix) {
+                *sample = T::from_sample(mixed.clamp(-1.0, 1.0));
+            }
+        }
+    }
+    match data.sample_format() {
+        SampleFormat::F32 => write::<f32>(data, mix),
+        SampleFormat::I32 => write::<i32>(data, mix),
+        SampleFormat::I24 => write::<I24>(data, mix),
+        SampleFormat::I16 => write::<i16>(data, mix),
+        format => unreachable!("mixers don't open their PCM as {}", format),
+    }
+}
+
+// Lock `mutex`, regardless of a callback having panicked while it was locked.
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Stop the worker thread of `mixer` and close its PCM, once `MIXERS` lists it as closing.
+//
+// This waits for the data and error callbacks to return, and drops the sources left, so `MIXERS`
+// must not be locked.
+fn close(mixer: Arc<Mixer>) {
+    let key = mixer.key.clone();
+    drop(mixer);
+    lock(&MIXERS).closing.retain(|closing| *closing != key);
+    SETTLED.notify_all();
+}
+
+/// An output stream built with [`Device::build_shared_output_stream`].
+///
+/// The mixer behind the stream outlives it as long as other streams share its PCM. Dropping the
+/// stream waits for a call of its data callback in progress to return, and drops the callback,
+/// unless it is dropped by a callback of the same mixer.
+pub struct SharedStream {
+    id: u64,
+    paused: Arc<AtomicBool>,
+    // Only `MIXERS` owns the mixer until it is closed, see `Drop`.
+    mixer: Weak<Mixer>,
+}
+
+impl Device {
+    /// Whether the PCM of this device accepts several streams at once.
+    ///
+    /// This opens the PCM for playback twice, and closes it right away. A PCM that is busy, e.g.
+    /// with the stream of another application, is exclusive.
+    pub fn sharing(&self) -> Result<Sharing, BuildStreamError> {
+        let open = || alsa::PCM::new(&self.pcm_id, alsa::Direction::Playback, true);
+        let _first = match open() {
+            Err(err) if err.errno() == libc::EBUSY => return Ok(Sharing::Exclusive),
+            first => first?,
+        };
+        match open() {
+            Ok(_) => Ok(Sharing::Shared),
+            Err(err) if err.errno() == libc::EBUSY => Ok(Sharing::Exclusive),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Build an output stream that shares the PCM of this device with the other streams built
+    /// this way, even if the PCM is [exclusive](Sharing::Exclusive).
+    ///
+    /// The streams are mixed as `f32` samples, and clipped to the range of the PCM's format. The
+    /// first of them opens the PCM with its config, while the others must use the same channel
+    /// count and sample rate, or fail with [`BuildStreamError::StreamConfigNotSupported`]. Streams
+    /// built on the `hw` and `plughw` devices of the same card device share the PCM of the first.
+    ///
+    /// Streams built with [`build_output_stream`](crate::traits::DeviceTrait::build_output_stream)
+    /// open the PCM on their own. If it is exclusive and a mixer already holds it, they are added
+    /// to the mixer instead, with the same restrictions on their config, and without a timeout or
+    /// [stats](StreamTrait::stats) of their own. A stream that opened an exclusive PCM on its own
+    /// keeps a mixer from opening it, so that this fails with
+    /// [`BuildStreamError::DeviceNotAvailable`].
+    pub fn build_shared_output_stream<D, E>(
+        &self,
+        config: &StreamConfig,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<SharedStream, BuildStreamError>
+    where
+        D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let mixer = self.join_mixer(config)?;
+        Ok(add_source(
+            mixer,
+            Box::new(data_callback),
+            Arc::new(Mutex::new(error_callback)),
+        ))
+    }
+
+    // Whether a mixer holds the PCM of this device, or is opening it.
+    pub(super) fn is_mixed(&self) -> bool {
+        let key = mixer_key(&self.pcm_id);
+        let mixers = lock(&MIXERS);
+        mixers.opening.contains(&key) || mixers.open.iter().any(|mixer| mixer.key == key)
+    }
+
+    // Build an output stream of `sample_format` that is added to the mixer of the PCM of this
+    // device like a shared stream, see `build_output_stream_raw`.
+    pub(super) fn build_mixed_output_stream<D, E>(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Stream, BuildStreamError>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let mixed_data_callback: fn(D, usize) -> DataCallback = match sample_format {
+            SampleFormat::I8 => mixed_data_callback::<i8, D>,
+            SampleFormat::I16 => mixed_data_callback::<i16, D>,
+            SampleFormat::I24 => mixed_data_callback::<I24, D>,
+            SampleFormat::I32 => mixed_data_callback::<i32, D>,
+            SampleFormat::I48 => mixed_data_callback::<I48, D>,
+            SampleFormat::I64 => mixed_data_callback::<i64, D>,
+            SampleFormat::U8 => mixed_data_callback::<u8, D>,
+            SampleFormat::U16 => mixed_data_callback::<u16, D>,
+            SampleFormat::U24 => mixed_data_callback::<U24, D>,
+            SampleFormat::U32 => mixed_data_callback::<u32, D>,
+            SampleFormat::U48 => mixed_data_callback::<U48, D>,
+            SampleFormat::U64 => mixed_data_callback::<u64, D>,
+            SampleFormat::F32 => mixed_data_callback::<f32, D>,
+            SampleFormat::F64 => mixed_data_callback::<f64, D>,
+            SampleFormat::MuLaw => mixed_data_callback::<MuLaw, D>,
+            SampleFormat::ALaw => mixed_data_callback::<ALaw, D>,
+            _ => return Err(BuildStreamError::StreamConfigNotSupported),
+        };
+        // Errors of the mixer are queued for the stream, as they are for streams of their own.
+        let (mut error_sender, errors) = error_queue("cpal_alsa_mixed_errors", error_callback);
+        let mixer = self.join_mixer(config)?;
+        let data_callback = mixed_data_callback(data_callback, mixer.buffer_samples);
+        let error_callback = move |err: StreamError| error_sender.send(err);
+        let stream = add_source(mixer, data_callback, Arc::new(Mutex::new(error_callback)));
+        Ok(Stream(StreamKind::Mixed(MixedStream {
+            stream,
+            sample_format,
+            errors,
+        })))
+    }
+
+    // The mixer of the PCM of this device, which is opened with `config` if there is none, and
+    // counts one more stream.
+    fn join_mixer(&self, config: &StreamConfig) -> Result<Arc<Mixer>, BuildStreamError> {
+        let key = mixer_key(&self.pcm_id);
+        let mut mixers = lock(&MIXERS);
+        let mixer = loop {
+            if mixers.closing.contains(&key) {
+                // The callbacks of a mixer can't wait, as the mixer being closed may be their own.
+                if MIXER_THREAD.with(Cell::get).is_some() {
+                    return Err(BuildStreamError::DeviceNotAvailable);
+                }
+            } else if !mixers.opening.contains(&key) {
+                if let Some(mixer) = mixers.open.iter().find(|mixer| mixer.key == key) {
+                    break mixer.clone();
+                }
+                // Opening the PCM may take a while, and other mixers are used meanwhile.
+                mixers.opening.push(key.clone());
+                drop(mixers);
+                let opened = Mixer::open(self, config);
+                mixers = lock(&MIXERS);
+                mixers.opening.retain(|opening| *opening != key);
+                SETTLED.notify_all();
+                let mixer = Arc::new(opened?);
+                mixers.open.push(mixer.clone());
+                break mixer;
+            }
+            mixers = SETTLED.wait(mixers).unwrap_or_else(PoisonError::into_inner);
+        };
+        if (mixer.config.channels, mixer.config.sample_rate)
+            != (config.channels, config.sample_rate)
+        {
+            return Err(BuildStreamError::StreamConfigNotSupported);
+        }
+        // The mixer is kept open from now on, even once `MIXERS` is unlocked.
+        mixer.streams.fetch_add(1, Ordering::Relaxed);
+        Ok(mixer)
+    }
+}
+
+// Add a stream with the given callbacks to `mixer`, which already counts it.
+fn add_source(
+    mixer: Arc<Mixer>,
+    data_callback: DataCallback,
+    error_callback: ErrorCallback,
+) -> SharedStream {
+    let id = NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed);
+    let paused = Arc::new(AtomicBool::new(false));
+    lock(&mixer.error_callbacks).push((id, error_callback));
+    let source = Source {
+        id,
+        paused: paused.clone(),
+        data_callback,
+        buffer: Vec::with_capacity(mixer.buffer_samples),
+    };
+    // The source comes back if the worker thread has stopped, and is dropped unlocked.
+    let unsent = lock(&mixer.commands).send(Command::Add(source)).err();
+    drop(unsent);
+    SharedStream {
+        id,
+        paused,
+        mixer: Arc::downgrade(&mixer),
+    }
+}
+
+// A data callback for a mixer, which has `data_callback` write samples of type `T` and converts
+// them to `f32`. The samples are buffered in up to `capacity` samples allocated up front.
+fn mixed_data_callback<T, D>(mut data_callback: D, capacity: usize) -> DataCallback
+where
+    T: SizedSample + Send + 'static,
+    f32: FromSample<T>,
+    D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+{
+    let mut samples: Vec<T> = Vec::with_capacity(capacity);
+    Box::new(move |mix: &mut [f32], info: &OutputCallbackInfo| {
+        samples.clear();
+        samples.resize(mix.len(), T::EQUILIBRIUM);
+        let mut data =
+            unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), samples.len(), T::FORMAT) };
+        data_callback(&mut data, info);
+        for (mixed, &sample) in mix.iter_mut().zip(&samples) {
+            *mixed = sample.to_sample();
+        }
+    })
+}
+
+impl SharedStream {
+    /// The number of streams sharing the PCM of this stream, including itself.
+    pub fn stream_count(&self) -> usize {
+        self.mixer
+            .upgrade()
+            .map_or(0, |mixer| mixer.streams.load(Ordering::Relaxed))
+    }
+}
+
+impl Drop for SharedStream {
+    fn drop(&mut self) {
+        let Some(mixer) = self.mixer.upgrade() else {
+            return;
+        };
+        // The worker thread sends the source back on its next period. Its callbacks can't wait
+        // for it, and leave the source to be dropped there. If the thread has stopped, the source
+        // is dropped with it, and the channel is closed.
+        let (retired_sender, retired) = mpsc::channel();
+        let remove = Command::Remove(self.id, retired_sender);
+        let unsent = lock(&mixer.commands).send(remove).err();
+        drop(unsent);
+        if MIXER_THREAD.with(Cell::get) != Some(mixer.id) {
+            drop(retired.recv());
+        }
+        drop(retired);
+        let error_callback = {
+            let mut error_callbacks = lock(&mixer.error_callbacks);
+            let index = error_callbacks.iter().position(|(id, _)| *id == self.id);
+            index.map(|index| error_callbacks.swap_remove(index))
+        };
+        drop(error_callback);
+
+        let mut mixers = lock(&MIXERS);
+        if mixer.streams.fetch_sub(1, Ordering::Relaxed) > 1 {
+            return;
+        }
+        mixers.open.retain(|other| !Arc::ptr_eq(other, &mixer));
+        mixers.closing.push(mixer.key.clone());
+        drop(mixers);
+        if MIXER_THREAD.with(Cell::get) == Some(mixer.id) {
+            // A callback of the mixer dropped its last stream, and its thread can't wait for
+            // itself to stop.
+            thread::spawn(move || close(mixer));
+        } else {
+            close(mixer);
+        }
+    }
+}
+
+impl StreamTrait for SharedStream {
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.paused.store(false, Ordering::Release);
+        Ok(())
+    }
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        // The PCM keeps running, and the other streams are still mixed.
+        self.paused.store(true, Ordering::Release);
+        Ok(())
+    }
+    fn can_pause(&self) -> bool {
+        true
+    }
+    fn stats(&self) -> Option<StreamStats> {
+        // The callbacks of the mixer cover every stream.
+        None
+    }
+    fn latency(&self) -> Option<Duration> {
+        self.mixer.upgrade()?.stream.latency()
+    }
+    fn negotiated_config(&self) -> Option<NegotiatedStreamConfig> {
+        // The stream writes `f32` samples, whichever format the mixer converts them to.
+        let negotiated = self.mixer.upgrade()?.stream.negotiated_config()?;
+        Some(NegotiatedStreamConfig {
+            sample_format: SampleFormat::F32,
+            ..negotiated
+        })
+    }
+}
+
+// An output stream built with `build_output_stream`, which was added to the mixer holding its
+// exclusive PCM, see `Device::build_mixed_output_stream`.
+pub(super) struct MixedStream {
+    stream: SharedStream,
+    // The format the data callback writes, before it is converted to `f32` for the mixer.
+    sample_format: SampleFormat,
+    // Dropped after `stream`, whose error callback holds the sender.
+    pub(super) errors: ErrorReceiver<StreamError>,
+}
+
+impl MixedStream {
+    pub(super) fn buffer_layout(&self) -> BufferLayout {
+        // The mixer is open as long as it counts this stream.
+        let mixer = self
+            .stream
+            .mixer
+            .upgrade()
+            .expect("mixer closed with streams left");
+        mixer.stream.inner.buffer_layout
+    }
+}
+
+impl StreamTrait for MixedStream {
+    fn play(&self) -> Result<(), PlayStreamError> {
+        self.stream.play()
+    }
+    fn pause(&self) -> Result<(), PauseStreamError> {
+        self.stream.pause()
+    }
+    fn can_pause(&self) -> bool {
+        self.stream.can_pause()
+    }
+    fn stats(&self) -> Option<StreamStats> {
+        self.stream.stats()
+    }
+    fn latency(&self) -> Option<Duration> {
+        self.stream.latency()
+    }
+    fn negotiated_config(&self) -> Option<NegotiatedStreamConfig> {
+        let negotiated = self.stream.negotiated_config()?;
+        Some(NegotiatedStreamConfig {
+            sample_format: self.sample_format,
+            ..negotiated
+        })
+    }
+}
+
+#[test]
+fn test_shared_output_streams() {
+    use super::{null_device, wait_for_callbacks};
+    use crate::{BufferSize, SampleRate};
+
+    let null = null_device();
+    assert_eq!(null.sharing().unwrap(), Sharing::Shared);
+
+    let config = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Fixed(256),
+    };
+    let build = |callbacks: Arc<AtomicUsize>| {
+        null.build_shared_output_stream(
+            &config,
+            move |data: &mut [f32], _: &OutputCallbackInfo| {
+                data.fill(0.25);
+                callbacks.fetch_add(1, Ordering::SeqCst);
+            },
+            |err| panic!("{}", err),
+        )
+        .unwrap()
+    };
+    let first_callbacks = Arc::new(AtomicUsize::new(0));
+    let second_callbacks = Arc::new(AtomicUsize::new(0));
+    let first = build(first_callbacks.clone());
+    let second = build(second_callbacks.clone());
+    assert_eq!(first.stream_count(), 2);
+    assert_eq!(
+        second.negotiated_config().unwrap().sample_format,
+        SampleFormat::F32
+    );
+
+    // Both streams are mixed on the same PCM.
+    wait_for_callbacks(&first_callbacks);
+    wait_for_callbacks(&second_callbacks);
+
+    // A dropped stream releases what its callback captured, while the mixer stays open.
+    let captured = Arc::new(());
+    let released = Arc::downgrade(&captured);
+    let third = null
+        .build_shared_output_stream(
+            &config,
+            move |data: &mut [f32], _: &OutputCallbackInfo| {
+                let _captured = &captured;
+                data.fill(0.0);
+            },
+            |err| panic!("{}", err),
+        )
+        .unwrap();
+    drop(third);
+    assert!(released.upgrade().is_none());
+    assert_eq!(first.stream_count(), 2);
+
+    // Streams of other formats are converted and mixed too, as `build_output_stream` does for
+    // exclusive PCMs held by a mixer.
+    let mixed_callbacks = Arc::new(AtomicUsize::new(0));
+    let counter = mixed_callbacks.clone();
+    let mixed = null
+        .build_mixed_output_stream(
+            &config,
+            SampleFormat::I16,
+            move |data: &mut Data, _: &OutputCallbackInfo| {
+                data.as_slice_mut::<i16>().unwrap().fill(0);
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+            |err| panic!("{}", err),
+        )
+        .unwrap();
+    assert_eq!(first.stream_count(), 3);
+    assert_eq!(
+        mixed.negotiated_config().unwrap().sample_format,
+        SampleFormat::I16
+    );
+    assert!(mixed.stats().is_none());
+    wait_for_callbacks(&mixed_callbacks);
+    drop(mixed);
+    assert_eq!(first.stream_count(), 2);
+
+    // Streams with another rate can't join the mixer.
+    let other_rate = StreamConfig {
+        sample_rate: SampleRate(44_100),
+        ..config.clone()
+    };
+    assert!(matches!(
+        null.build_shared_output_stream(&other_rate, |_: &mut [f32], _: &_| {}, |_| {}),
+        Err(BuildStreamError::StreamConfigNotSupported)
+    ));
+
+    drop(first);
+    assert_eq!(second.stream_count(), 1);
+    drop(second);
+    assert!(!lock(&MIXERS).open.iter().any(|mixer| mixer.key == "null"));
+
+    // Callbacks can build and drop streams of their own mixer, without waiting for the worker
+    // thread they run on. They stop before the stream is dropped, which doesn't wait for them.
+    let building = Arc::new(AtomicBool::new(true));
+    let callbacks = Arc::new(AtomicUsize::new(0));
+    let (device, stream_config) = (null.clone(), config.clone());
+    let (callback_building, counter) = (building.clone(), callbacks.clone());
+    let stream = null
+        .build_shared_output_stream(
+            &config,
+            move |data: &mut [f32], _: &OutputCallbackInfo| {
+                data.fill(0.0);
+                if callback_building.load(Ordering::SeqCst) {
+                    let other = device
+                        .build_shared_output_stream(
+                            &stream_config,
+                            |data: &mut [f32], _: &OutputCallbackInfo| data.fill(0.0),
+                            |err| panic!("{}", err),
+                        )
+                        .unwrap();
+                    drop(other);
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+            |err| panic!("{}", err),
+        )
+        .unwrap();
+    wait_for_callbacks(&callbacks);
+    building.store(false, Ordering::SeqCst);
+    wait_for_callbacks(&callbacks);
+    assert_eq!(stream.stream_count(), 1);
+}
+
+#[test]
+fn test_mixer_key() {
+    assert_eq!(
+        mixer_key("hw:CARD=cpal_missing,DEV=3"),
+        "hw:cpal_missing,3,-1"
+    );
+    assert_eq!(
+        mixer_key("plughw:cpal_missing,3"),
+        mixer_key("hw:CARD=cpal_missing,DEV=3")
+    );
+    assert_eq!(mixer_key("hw:cpal_missing,0,1"), "hw:cpal_missing,0,1");
+    assert_eq!(mixer_key("hw:cpal_missing"), "hw:cpal_missing,0,-1");
+    assert_eq!(mixer_key("null"), "null");
+}
+
+#[test]
+fn test_supported_mix_format() {
+    use crate::{SampleRate, SupportedBufferSize};
+
+    // A wide card whose channel count is a range, and which only takes integer samples.
+    let ranges = [SampleFormat::I16, SampleFormat::I32].map(|format| {
+        SupportedStreamConfigRange::with_channel_range(
+            1,
+            64,
+            SampleRate(44_100),
+            SampleRate(48_000),
+            SupportedBufferSize::Unknown,
+            format,
+        )
+    });
+    let mut config = StreamConfig {
+        channels: 8,
+        sample_rate: SampleRate(48_000),
+        buffer_size: crate::BufferSize::Default,
+    };
+    assert_eq!(
+        supported_mix_format(&ranges, &config),
+        Some(SampleFormat::I32)
+    );
+    config.channels = 65;
+    assert_eq!(supported_mix_format(&ranges, &config), None);
+    config.channels = 64;
+    config.sample_rate = SampleRate(96_000);
+    assert_eq!(supported_mix_format(&ranges, &config), None);
+}
+
+#[test]
+fn test_write_mix() {
+    let mut samples = [0i16; 3];
+    let mut data =
+        unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 3, SampleFormat::I16) };
+    write_mix(&mut data, &[0.5, 1.5, -2.0]);
+    assert_eq!(samples, [16384, i16::MAX, i16::MIN]);
+}
+
+#[test]
+fn test_mixed_data_callback() {
+    use crate::{OutputStreamTimestamp, StreamInstant};
+
+    let mut data_callback = mixed_data_callback::<i16, _>(
+        |data: &mut Data, _: &OutputCallbackInfo| {
+            let samples = data.as_slice_mut::<i16>().unwrap();
+            samples.copy_from_slice(&[i16::MIN, 0, 16384]);
+        },
+        3,
+    );
+    let instant = StreamInstant::new(0, 0);
+    let info = OutputCallbackInfo::new(OutputStreamTimestamp {
+        callback: instant,
+        playback: instant,
+    });
+    let mut mix = [1.0; 3];
+    data_callback(&mut mix, &info);
+    assert_eq!(mix, [-1.0, 0.0, 0.5]);
+}
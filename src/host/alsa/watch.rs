@@ -0,0 +1,188 @@
+//! Notifications of sound cards being added or removed.
+//!
+//! A thread subscribes to the events of the control device of every card, whose descriptors
+//! report an error as soon as the card is removed. Cards added in the meantime are found by
+//! rescanning the list of cards, which doesn't open any of their PCMs.
+//!
+//! The PCMs of a card added or removed are reported the way the host lists them, i.e. through
+//! `hw`, `plughw` or both depending on its `CardAccess`, see `enumerate::card_pcms`. Changes of
+//! the default device can't be told, as it is a PCM of the configuration rather than a card.
+
+use super::alsa;
+use super::alsa::poll::Descriptors;
+use super::enumerate::{card_pcms, device_id, CardAccess};
+use super::{trigger, TriggerReceiver, TriggerSender};
+use crate::{BackendSpecificError, DeviceEvent, DeviceId, DeviceWatcher, WatchDevicesError};
+use std::collections::BTreeMap;
+use std::thread::{self, JoinHandle};
+
+// How often the list of cards is rescanned if no event wakes the thread earlier.
+const RESCAN_INTERVAL_MS: i32 = 1000;
+
+// A card watched through its control device.
+struct Card {
+    // The ids and names of the devices of the card, as returned by `Device::id` and `Device::name`.
+    // The ids are found while the card is present, as they can't be once it is removed.
+    devices: Vec<(DeviceId, String)>,
+    ctl: alsa::Ctl,
+}
+
+impl Card {
+    fn open(card: &alsa::Card, card_access: CardAccess) -> Result<Self, alsa::Error> {
+        // Events are read until none is left, which must not block.
+        let ctl = alsa::Ctl::from_card(card, true)?;
+        let devices = card_pcms(card, card_access)?
+            .into_iter()
+            .map(|pcm| Ok((DeviceId::from(device_id(&pcm.pcm_id)?), pcm.name)))
+            .collect::<Result<_, alsa::Error>>()?;
+        ctl.subscribe_events(true)?;
+        Ok(Card { devices, ctl })
+    }
+
+    fn report_removed(self, callback: &mut dyn FnMut(DeviceEvent)) {
+        for (id, name) in self.devices {
+            callback(DeviceEvent::Removed { id, name });
+        }
+    }
+}
+
+// Stops the watching thread when dropped.
+struct Watch {
+    trigger: TriggerSender,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.trigger.wakeup();
+        self.thread.take().unwrap().join().unwrap();
+    }
+}
+
+pub(super) fn watch_devices<F>(
+    card_access: CardAccess,
+    mut callback: F,
+) -> Result<DeviceWatcher, WatchDevicesError>
+where
+    F: FnMut(DeviceEvent) + Send + 'static,
+{
+    // The cards present from the start are not reported.
+    let mut cards = BTreeMap::new();
+    rescan(&mut cards, card_access, &mut |_| {}).map_err(BackendSpecificError::from)?;
+
+    let (trigger, rx) = trigger();
+    let thread = thread::Builder::new()
+        .name("cpal_alsa_watch".to_owned())
+        .spawn(move || watch_worker(rx, cards, card_access, &mut callback))
+        .unwrap();
+    Ok(DeviceWatcher::new(Watch {
+        trigger,
+        thread: Some(thread),
+    }))
+}
+
+fn watch_worker(
+    rx: TriggerReceiver,
+    mut cards: BTreeMap<i32, Card>,
+    card_access: CardAccess,
+    callback: &mut dyn FnMut(DeviceEvent),
+) {
+    let mut descriptors = Vec::new();
+    loop {
+        descriptors.clear();
+        descriptors.push(libc::pollfd {
+            fd: rx.0,
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        let mut ranges = Vec::with_capacity(cards.len());
+        for card in cards.values() {
+            let start = descriptors.len();
+            // A card whose descriptors can't be added is still found missing by `rescan`.
+            if let Ok(fds) = Descriptors::get(&card.ctl) {
+                descriptors.extend(fds);
+            }
+            ranges.push(start..descriptors.len());
+        }
+
+        if alsa::poll::poll(&mut descriptors, RESCAN_INTERVAL_MS).is_err() {
+            // Interrupted by a signal, or out of memory. Try again later.
+            thread::sleep(std::time::Duration::from_millis(RESCAN_INTERVAL_MS as u64));
+        }
+        if descriptors[0].revents != 0 {
+            // The watcher has been dropped.
+            rx.clear_pipe();
+            return;
+        }
+
+        let mut removed = Vec::new();
+        for ((&index, card), range) in cards.iter().zip(ranges) {
+            match card.ctl.revents(&descriptors[range]) {
+                Ok(flags) if !flags.intersects(alsa::poll::Flags::ERR | alsa::poll::Flags::HUP) => {
+                    // Element events, such as a volume change, don't concern the devices.
+                    while let Ok(Some(_)) = card.ctl.read() {}
+                }
+                _ => removed.push(index),
+            }
+        }
+        for index in removed {
+            cards.remove(&index).unwrap().report_removed(callback);
+        }
+
+        // Listing the cards fails only if ALSA itself is misconfigured, which would keep the
+        // watcher from telling anything anyway.
+        let _ = rescan(&mut cards, card_access, callback);
+    }
+}
+
+// Update `cards` to the cards currently present, and report those added or removed.
+fn rescan(
+    cards: &mut BTreeMap<i32, Card>,
+    card_access: CardAccess,
+    callback: &mut dyn FnMut(DeviceEvent),
+) -> Result<(), alsa::Error> {
+    let mut present = Vec::new();
+    for card in alsa::card::Iter::new() {
+        let card = card?;
+        let index = card.get_index();
+        if cards.contains_key(&index) {
+            present.push(index);
+            continue;
+        }
+        // A card that is being removed can still be listed, but its control device can't be
+        // opened anymore.
+        if let Ok(card) = Card::open(&card, card_access) {
+            for (id, name) in &card.devices {
+                callback(DeviceEvent::Added {
+                    id: id.clone(),
+                    name: name.clone(),
+                });
+            }
+            cards.insert(index, card);
+            present.push(index);
+        }
+    }
+
+    let removed: Vec<i32> = cards
+        .keys()
+        .filter(|index| !present.contains(index))
+        .copied()
+        .collect();
+    for index in removed {
+        cards.remove(&index).unwrap().report_removed(callback);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_watch_devices() {
+    use std::sync::mpsc;
+
+    let (tx, rx) = mpsc::channel();
+    let watcher = watch_devices(CardAccess::Both, move |event| tx.send(event).unwrap()).unwrap();
+    // Without any card being plugged in or out, nothing is reported, and dropping the watcher
+    // stops its thread.
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    drop(watcher);
+    assert!(rx.recv().is_err());
+}
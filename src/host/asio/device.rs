@@ -80,7 +80,8 @@ impl Device {
             }
             for channels in 1..f.channels + 1 {
                 supported_configs.push(SupportedStreamConfigRange {
-                    channels,
+                    min_channels: channels,
+                    max_channels: channels,
                     min_sample_rate: rate,
                     max_sample_rate: rate,
                     buffer_size: f.buffer_size,
@@ -117,7 +118,8 @@ impl Device {
             }
             for channels in 1..f.channels + 1 {
                 supported_configs.push(SupportedStreamConfigRange {
-                    channels,
+                    min_channels: channels,
+                    max_channels: channels,
                     min_sample_rate: rate,
                     max_sample_rate: rate,
                     buffer_size: f.buffer_size,
@@ -84,7 +84,8 @@ impl Device {
         let asbd: AudioStreamBasicDescription = default_input_asbd()?;
         let stream_config = stream_config_from_asbd(asbd);
         Ok(vec![SupportedStreamConfigRange {
-            channels: stream_config.channels,
+            min_channels: stream_config.channels,
+            max_channels: stream_config.channels,
             min_sample_rate: stream_config.sample_rate,
             max_sample_rate: stream_config.sample_rate,
             buffer_size: stream_config.buffer_size.clone(),
@@ -105,7 +106,8 @@ impl Device {
 
         let configs: Vec<_> = (1..=asbd.mChannelsPerFrame as u16)
             .map(|channels| SupportedStreamConfigRange {
-                channels,
+                min_channels: channels,
+                max_channels: channels,
                 min_sample_rate: stream_config.sample_rate,
                 max_sample_rate: stream_config.sample_rate,
                 buffer_size: stream_config.buffer_size.clone(),
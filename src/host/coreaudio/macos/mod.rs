@@ -289,7 +289,8 @@ impl Device {
             let mut fmts = vec![];
             for range in ranges {
                 let fmt = SupportedStreamConfigRange {
-                    channels: n_channels as ChannelCount,
+                    min_channels: n_channels as ChannelCount,
+                    max_channels: n_channels as ChannelCount,
                     min_sample_rate: SampleRate(range.mMinimum as _),
                     max_sample_rate: SampleRate(range.mMaximum as _),
                     buffer_size,
@@ -0,0 +1,226 @@
+//! Delivery of stream errors away from realtime audio threads.
+//!
+//! An audio thread sends errors through an [`ErrorSender`], which writes them into a preallocated
+//! ring buffer without locking or allocating. A separate thread turns them into [`StreamError`]s
+//! and passes them to the user's error callback. Alternatively, the user can poll them through the
+//! [`ErrorReceiver`] held by the stream.
+
+use crate::StreamError;
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle, Thread};
+
+/// The number of errors that can be queued before further errors are dropped.
+const CAPACITY: usize = 64;
+
+/// Create a queue whose errors are delivered to `error_callback` on a new thread named
+/// `thread_name`.
+pub(crate) fn error_queue<T, E>(
+    thread_name: &str,
+    mut error_callback: E,
+) -> (ErrorSender<T>, ErrorReceiver<T>)
+where
+    T: Into<StreamError> + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let queue = Arc::new(Queue::<T>::new());
+    let delivery_queue = queue.clone();
+    let delivery = thread::Builder::new()
+        .name(thread_name.to_owned())
+        .spawn(move || loop {
+            // Errors sent before the sender was dropped are still delivered.
+            let closed = delivery_queue.closed.load(Ordering::Acquire);
+            while let Some(err) = delivery_queue.pop_for_callback() {
+                error_callback(err.into());
+            }
+            if closed {
+                return;
+            }
+            thread::park();
+        })
+        .unwrap();
+    let sender = ErrorSender {
+        queue: queue.clone(),
+        delivery: delivery.thread().clone(),
+    };
+    let receiver = ErrorReceiver {
+        queue,
+        delivery: Some(delivery),
+    };
+    (sender, receiver)
+}
+
+/// The sending half of an error queue, used by the audio thread.
+pub(crate) struct ErrorSender<T> {
+    queue: Arc<Queue<T>>,
+    delivery: Thread,
+}
+
+impl<T> ErrorSender<T> {
+    /// Queue `err` for delivery. This never blocks or allocates, and drops `err` if the queue is
+    /// full.
+    pub fn send(&mut self, err: T) {
+        // Only one thread at a time can push, as this takes `&mut self`.
+        if self.queue.push(err).is_ok() {
+            self.delivery.unpark();
+        }
+    }
+}
+
+impl<T> Drop for ErrorSender<T> {
+    fn drop(&mut self) {
+        self.queue.closed.store(true, Ordering::Release);
+        self.delivery.unpark();
+    }
+}
+
+/// The receiving half of an error queue, held by the stream.
+///
+/// Dropping it waits until the delivery thread has passed on the remaining errors, so the
+/// [`ErrorSender`] must be dropped first.
+pub(crate) struct ErrorReceiver<T> {
+    queue: Arc<Queue<T>>,
+    delivery: Option<JoinHandle<()>>,
+}
+
+impl<T: Into<StreamError>> ErrorReceiver<T> {
+    /// Stop passing errors to the error callback, and keep them for [`try_recv`](Self::try_recv)
+    /// instead.
+    ///
+    /// Once this returns, the delivery thread takes no more errors from the queue, though it may
+    /// still be passing one on.
+    pub fn poll(&self) {
+        let _consumer = self.queue.lock_consumer();
+        self.queue.polled.store(true, Ordering::Relaxed);
+    }
+
+    /// The oldest queued error, if any.
+    pub fn try_recv(&self) -> Option<StreamError> {
+        self.queue.pop().map(Into::into)
+    }
+}
+
+impl<T> Drop for ErrorReceiver<T> {
+    fn drop(&mut self) {
+        if let Some(delivery) = self.delivery.take() {
+            let _ = delivery.join();
+        }
+    }
+}
+
+// A bounded single-producer ring buffer. The producer is the `ErrorSender`, while the delivery
+// thread and `ErrorReceiver::try_recv` take turns consuming through `consumer`.
+struct Queue<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    // The number of values popped so far, only advanced by the consumer.
+    head: AtomicUsize,
+    // The number of values pushed so far, only advanced by the producer.
+    tail: AtomicUsize,
+    consumer: Mutex<()>,
+    // Whether errors are polled rather than passed to the error callback. Only changed while
+    // holding the `consumer` lock.
+    polled: AtomicBool,
+    // Whether the sender has been dropped.
+    closed: AtomicBool,
+}
+
+// Values are only ever accessed by one thread at a time, see `push` and `pop`.
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Queue<T> {
+    fn new() -> Self {
+        // The indices wrap around, which only keeps slots in order for a power of two.
+        debug_assert!(CAPACITY.is_power_of_two());
+        Queue {
+            slots: (0..CAPACITY)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            consumer: Mutex::new(()),
+            polled: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    // Must only be called by one thread at a time.
+    fn push(&self, value: T) -> Result<(), T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == CAPACITY {
+            return Err(value);
+        }
+        // The consumer doesn't read this slot until `tail` is advanced past it.
+        unsafe { (*self.slots[tail % CAPACITY].get()).write(value) };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    fn lock_consumer(&self) -> MutexGuard<'_, ()> {
+        self.consumer.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn pop_for_callback(&self) -> Option<T> {
+        let _consumer = self.lock_consumer();
+        if self.polled.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.pop_locked()
+    }
+
+    fn pop(&self) -> Option<T> {
+        let _consumer = self.lock_consumer();
+        self.pop_locked()
+    }
+
+    // Must only be called while holding the `consumer` lock.
+    fn pop_locked(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        // The producer doesn't write this slot again until `head` is advanced past it.
+        let value = unsafe { (*self.slots[head % CAPACITY].get()).assume_init_read() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[test]
+fn test_error_queue() {
+    use crate::BackendSpecificError;
+    use std::sync::mpsc;
+
+    let error = |i: usize| StreamError::BackendSpecific {
+        err: BackendSpecificError {
+            description: i.to_string(),
+        },
+    };
+    let (tx, rx) = mpsc::channel();
+    let (mut sender, receiver) = error_queue("test_error_queue", move |err| {
+        tx.send(err).unwrap();
+    });
+    sender.send(error(0));
+    assert_eq!(rx.recv(), Ok(error(0)));
+
+    receiver.poll();
+    for i in 0..CAPACITY + 1 {
+        sender.send(error(i));
+    }
+    let polled: Vec<_> = std::iter::from_fn(|| receiver.try_recv()).collect();
+    assert_eq!(polled, (0..CAPACITY).map(error).collect::<Vec<_>>());
+
+    drop(sender);
+    drop(receiver);
+    assert!(rx.try_recv().is_err());
+}
@@ -1,9 +1,9 @@
 use crate::traits::DeviceTrait;
 use crate::{
     BackendSpecificError, BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError,
-    InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig, StreamError,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    DuplexCallbackInfo, DuplexStreamConfig, InputCallbackInfo, OutputCallbackInfo, SampleFormat,
+    SampleRate, StreamConfig, StreamError, SupportedBufferSize, SupportedStreamConfig,
+    SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 use std::hash::{Hash, Hasher};
 use std::time::Duration;
@@ -18,7 +18,7 @@ const DEFAULT_NUM_CHANNELS: u16 = 2;
 const DEFAULT_SUPPORTED_CHANNELS: [u16; 10] = [1, 2, 4, 6, 8, 16, 24, 32, 48, 64];
 
 /// If a device is for input or output.
-/// Until we have duplex stream support JACK clients and CPAL devices for JACK will be either input or output.
+/// JACK clients and CPAL devices for JACK are either input or output, though either can also build duplex streams.
 #[derive(Clone, Debug)]
 pub enum DeviceType {
     InputDevice,
@@ -118,7 +118,8 @@ impl Device {
 
         for &channels in DEFAULT_SUPPORTED_CHANNELS.iter() {
             supported_configs.push(SupportedStreamConfigRange {
-                channels,
+                min_channels: channels,
+                max_channels: channels,
                 min_sample_rate: f.sample_rate,
                 max_sample_rate: f.sample_rate,
                 buffer_size: f.buffer_size.clone(),
@@ -250,6 +251,45 @@ impl DeviceTrait for Device {
 
         Ok(stream)
     }
+
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        conf: &DuplexStreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        _timeout: Option<Duration>,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        if conf.sample_rate != self.sample_rate || sample_format != JACK_SAMPLE_FORMAT {
+            return Err(BuildStreamError::StreamConfigNotSupported);
+        }
+
+        // The settings should be fine, create a Client with both input and output ports
+        let client_options = super::get_client_options(self.start_server_automatically);
+        let client = super::get_client(&self.name, client_options).map_err(|e| {
+            BuildStreamError::BackendSpecific {
+                err: BackendSpecificError { description: e },
+            }
+        })?;
+        let mut stream = Stream::new_duplex(
+            client,
+            conf.input_channels,
+            conf.output_channels,
+            data_callback,
+            error_callback,
+        );
+
+        if self.connect_ports_automatically {
+            stream.connect_to_system_inputs();
+            stream.connect_to_system_outputs();
+        }
+
+        Ok(stream)
+    }
 }
 
 impl PartialEq for Device {
@@ -1,12 +1,16 @@
 extern crate jack;
 
-use crate::traits::HostTrait;
-use crate::{DevicesError, SampleFormat, SupportedStreamConfigRange};
+use crate::traits::{DeviceTrait, HostTrait};
+use crate::{
+    BackendSpecificError, DeviceEvent, DeviceWatcher, DevicesError, SampleFormat,
+    SupportedStreamConfigRange, WatchDevicesError,
+};
 
 mod device;
 pub use self::device::Device;
 pub use self::stream::Stream;
 mod stream;
+mod watch;
 
 const JACK_SAMPLE_FORMAT: SampleFormat = SampleFormat::F32;
 
@@ -129,6 +133,26 @@ impl HostTrait for Host {
         }
         None
     }
+
+    fn watch_devices<F>(&self, callback: F) -> Result<DeviceWatcher, WatchDevicesError>
+    where
+        F: FnMut(DeviceEvent) + Send + 'static,
+    {
+        // Notifications are received by a client of their own, whose name is derived like the
+        // ones of the devices.
+        let client_options = get_client_options(self.start_server_automatically);
+        let client = get_client(&format!("{}_watch", self.name), client_options)
+            .map_err(|description| BackendSpecificError { description })?;
+        let device_info = |device: Option<Device>| {
+            device.and_then(|device| Some((device.id().ok()?, device.name().ok()?)))
+        };
+        watch::watch_devices(
+            client,
+            device_info(self.default_input_device()),
+            device_info(self.default_output_device()),
+            callback,
+        )
+    }
 }
 
 fn get_client_options(start_server_automatically: bool) -> jack::ClientOptions {
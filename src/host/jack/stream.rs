@@ -2,15 +2,19 @@ use crate::traits::StreamTrait;
 use crate::ChannelCount;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::{
-    BackendSpecificError, Data, InputCallbackInfo, OutputCallbackInfo, PauseStreamError,
-    PlayStreamError, SampleRate, StreamError,
+    BackendSpecificError, BufferSize, Data, DuplexCallbackInfo, InputCallbackInfo,
+    NegotiatedStreamConfig, OutputCallbackInfo, PauseStreamError, PlayStreamError, SampleRate,
+    StreamConfig, StreamError, StreamStats,
 };
 
 use super::JACK_SAMPLE_FORMAT;
+use crate::host::error_queue::{error_queue, ErrorReceiver, ErrorSender};
+use crate::host::stats::StatsRecorder;
 
-type ErrorCallbackPtr = Arc<Mutex<dyn FnMut(StreamError) + Send + 'static>>;
+type ErrorSenderPtr = Arc<Mutex<ErrorSender<StreamError>>>;
 
 pub struct Stream {
     // TODO: It might be faster to send a message when playing/pausing than to check this every iteration
@@ -19,6 +23,9 @@ pub struct Stream {
     // Port names are stored in order to connect them to other ports in jack automatically
     input_port_names: Vec<String>,
     output_port_names: Vec<String>,
+    stats: Arc<StatsRecorder>,
+    // Dropped after `async_client`, which holds the sending half.
+    errors: ErrorReceiver<StreamError>,
 }
 
 impl Stream {
@@ -33,48 +40,30 @@ impl Stream {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        let mut ports = vec![];
-        let mut port_names: Vec<String> = vec![];
-        // Create ports
-        for i in 0..channels {
-            let port_try = client.register_port(&format!("in_{}", i), jack::AudioIn::default());
-            match port_try {
-                Ok(port) => {
-                    // Get the port name in order to later connect it automatically
-                    if let Ok(port_name) = port.name() {
-                        port_names.push(port_name);
-                    }
-                    // Store the port into a Vec to move to the ProcessHandler
-                    ports.push(port);
-                }
-                Err(e) => {
-                    // If port creation failed, send the error back via the error_callback
-                    error_callback(
-                        BackendSpecificError {
-                            description: e.to_string(),
-                        }
-                        .into(),
-                    );
-                }
-            }
-        }
+        let (ports, port_names) = register_ports(&client, "in", channels, &mut error_callback);
 
         let playing = Arc::new(AtomicBool::new(true));
+        let stats = Arc::new(StatsRecorder::new(frames_to_duration(
+            client.buffer_size() as usize,
+            SampleRate(client.sample_rate()),
+        )));
 
-        let error_callback_ptr = Arc::new(Mutex::new(error_callback)) as ErrorCallbackPtr;
+        let (error_sender, errors) = error_queue("cpal_jack_errors", error_callback);
+        let error_sender = Arc::new(Mutex::new(error_sender));
 
         let input_process_handler = LocalProcessHandler::new(
             vec![],
             ports,
-            SampleRate(client.sample_rate() as u32),
+            SampleRate(client.sample_rate()),
             client.buffer_size() as usize,
-            Some(Box::new(data_callback)),
-            None,
+            DataCallback::Input(Box::new(data_callback)),
             playing.clone(),
-            Arc::clone(&error_callback_ptr),
+            stats.clone(),
+            Arc::clone(&error_sender),
         );
 
-        let notification_handler = JackNotificationHandler::new(error_callback_ptr);
+        let notification_handler =
+            JackNotificationHandler::new(error_sender, stats.clone(), StreamDirection::Input);
 
         let async_client = client
             .activate_async(notification_handler, input_process_handler)
@@ -85,6 +74,8 @@ impl Stream {
             async_client,
             input_port_names: port_names,
             output_port_names: vec![],
+            stats,
+            errors,
         }
     }
 
@@ -98,48 +89,30 @@ impl Stream {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static,
     {
-        let mut ports = vec![];
-        let mut port_names: Vec<String> = vec![];
-        // Create ports
-        for i in 0..channels {
-            let port_try = client.register_port(&format!("out_{}", i), jack::AudioOut::default());
-            match port_try {
-                Ok(port) => {
-                    // Get the port name in order to later connect it automatically
-                    if let Ok(port_name) = port.name() {
-                        port_names.push(port_name);
-                    }
-                    // Store the port into a Vec to move to the ProcessHandler
-                    ports.push(port);
-                }
-                Err(e) => {
-                    // If port creation failed, send the error back via the error_callback
-                    error_callback(
-                        BackendSpecificError {
-                            description: e.to_string(),
-                        }
-                        .into(),
-                    );
-                }
-            }
-        }
+        let (ports, port_names) = register_ports(&client, "out", channels, &mut error_callback);
 
         let playing = Arc::new(AtomicBool::new(true));
+        let stats = Arc::new(StatsRecorder::new(frames_to_duration(
+            client.buffer_size() as usize,
+            SampleRate(client.sample_rate()),
+        )));
 
-        let error_callback_ptr = Arc::new(Mutex::new(error_callback)) as ErrorCallbackPtr;
+        let (error_sender, errors) = error_queue("cpal_jack_errors", error_callback);
+        let error_sender = Arc::new(Mutex::new(error_sender));
 
         let output_process_handler = LocalProcessHandler::new(
             ports,
             vec![],
-            SampleRate(client.sample_rate() as u32),
+            SampleRate(client.sample_rate()),
             client.buffer_size() as usize,
-            None,
-            Some(Box::new(data_callback)),
+            DataCallback::Output(Box::new(data_callback)),
             playing.clone(),
-            Arc::clone(&error_callback_ptr),
+            stats.clone(),
+            Arc::clone(&error_sender),
         );
 
-        let notification_handler = JackNotificationHandler::new(error_callback_ptr);
+        let notification_handler =
+            JackNotificationHandler::new(error_sender, stats.clone(), StreamDirection::Output);
 
         let async_client = client
             .activate_async(notification_handler, output_process_handler)
@@ -150,9 +123,79 @@ impl Stream {
             async_client,
             input_port_names: vec![],
             output_port_names: port_names,
+            stats,
+            errors,
+        }
+    }
+
+    pub fn new_duplex<D, E>(
+        client: jack::Client,
+        input_channels: ChannelCount,
+        output_channels: ChannelCount,
+        data_callback: D,
+        mut error_callback: E,
+    ) -> Stream
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        let (in_ports, input_port_names) =
+            register_ports(&client, "in", input_channels, &mut error_callback);
+        let (out_ports, output_port_names) =
+            register_ports(&client, "out", output_channels, &mut error_callback);
+
+        let playing = Arc::new(AtomicBool::new(true));
+        let stats = Arc::new(StatsRecorder::new(frames_to_duration(
+            client.buffer_size() as usize,
+            SampleRate(client.sample_rate()),
+        )));
+
+        let (error_sender, errors) = error_queue("cpal_jack_errors", error_callback);
+        let error_sender = Arc::new(Mutex::new(error_sender));
+
+        let duplex_process_handler = LocalProcessHandler::new(
+            out_ports,
+            in_ports,
+            SampleRate(client.sample_rate()),
+            client.buffer_size() as usize,
+            DataCallback::Duplex(Box::new(data_callback)),
+            playing.clone(),
+            stats.clone(),
+            Arc::clone(&error_sender),
+        );
+
+        let notification_handler =
+            JackNotificationHandler::new(error_sender, stats.clone(), StreamDirection::Duplex);
+
+        let async_client = client
+            .activate_async(notification_handler, duplex_process_handler)
+            .unwrap();
+
+        Stream {
+            playing,
+            async_client,
+            input_port_names,
+            output_port_names,
+            stats,
+            errors,
         }
     }
 
+    /// Stop passing errors to the error callback, and queue them to be retrieved with
+    /// [`try_recv_error`](Self::try_recv_error) instead.
+    pub fn poll_errors(&self) {
+        self.errors.poll();
+    }
+
+    /// The oldest error the stream raised since [`poll_errors`](Self::poll_errors) was called, if
+    /// any.
+    ///
+    /// Only a limited number of errors is queued, and further errors are dropped until they are
+    /// retrieved.
+    pub fn try_recv_error(&self) -> Option<StreamError> {
+        self.errors.try_recv()
+    }
+
     /// Connect to the standard system outputs in jack, system:playback_1 and system:playback_2
     /// This has to be done after the client is activated, doing it just after creating the ports doesn't work.
     pub fn connect_to_system_outputs(&mut self) {
@@ -216,10 +259,112 @@ impl StreamTrait for Stream {
         self.playing.store(false, Ordering::SeqCst);
         Ok(())
     }
+
+    fn can_pause(&self) -> bool {
+        // The process callback skips the stream while it is paused, which the JACK server keeps
+        // running.
+        true
+    }
+
+    fn stats(&self) -> Option<StreamStats> {
+        Some(self.stats.snapshot())
+    }
+
+    fn latency(&self) -> Option<Duration> {
+        let client = self.async_client.as_client();
+        // JACK gives each port the range of latencies of the physical ports it is connected to,
+        // through any other clients. Take the longest of them in each direction.
+        let port_latency = |port_names: &[String], mode| {
+            port_names
+                .iter()
+                .filter_map(|name| client.port_by_name(name))
+                .map(|port| port.get_latency_range(mode).1)
+                .max()
+                .unwrap_or(0)
+        };
+        let frames = port_latency(&self.input_port_names, jack::LatencyType::Capture)
+            + port_latency(&self.output_port_names, jack::LatencyType::Playback)
+            + client.buffer_size();
+        Some(frames_to_duration(
+            frames as usize,
+            SampleRate(client.sample_rate()),
+        ))
+    }
+
+    fn negotiated_config(&self) -> Option<NegotiatedStreamConfig> {
+        // The server decides the rate and buffer size, and passes the whole buffer to each
+        // callback. Either may change while the stream runs.
+        let client = self.async_client.as_client();
+        let inputs = self.input_port_names.len() as ChannelCount;
+        let (channels, input_channels) = match self.output_port_names.len() as ChannelCount {
+            0 => (inputs, None),
+            outputs if inputs == 0 => (outputs, None),
+            outputs => (outputs, Some(inputs)),
+        };
+        Some(NegotiatedStreamConfig {
+            config: StreamConfig {
+                channels,
+                sample_rate: SampleRate(client.sample_rate()),
+                buffer_size: BufferSize::Fixed(client.buffer_size()),
+            },
+            input_channels,
+            sample_format: JACK_SAMPLE_FORMAT,
+            period_size: client.buffer_size(),
+        })
+    }
+}
+
+// Register `channels` ports named `{prefix}_{i}` with `client`, along with their full names,
+// which are needed to connect them once the client is activated.
+//
+// Ports that can't be registered are left out and reported to `error_callback`.
+fn register_ports<P, E>(
+    client: &jack::Client,
+    prefix: &str,
+    channels: ChannelCount,
+    error_callback: &mut E,
+) -> (Vec<jack::Port<P>>, Vec<String>)
+where
+    P: jack::PortSpec + Default,
+    E: FnMut(StreamError),
+{
+    let mut ports = vec![];
+    let mut port_names: Vec<String> = vec![];
+    for i in 0..channels {
+        let port_try = client.register_port(&format!("{}_{}", prefix, i), P::default());
+        match port_try {
+            Ok(port) => {
+                // Get the port name in order to later connect it automatically
+                if let Ok(port_name) = port.name() {
+                    port_names.push(port_name);
+                }
+                // Store the port into a Vec to move to the ProcessHandler
+                ports.push(port);
+            }
+            Err(e) => {
+                // If port creation failed, send the error back via the error_callback
+                error_callback(
+                    BackendSpecificError {
+                        description: e.to_string(),
+                    }
+                    .into(),
+                );
+            }
+        }
+    }
+    (ports, port_names)
 }
 
 type InputDataCallback = Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>;
 type OutputDataCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>;
+type DuplexDataCallback = Box<dyn FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static>;
+
+/// The user's data callback, which decides the direction of the stream.
+enum DataCallback {
+    Input(InputDataCallback),
+    Output(OutputDataCallback),
+    Duplex(DuplexDataCallback),
+}
 
 struct LocalProcessHandler {
     /// No new ports are allowed to be created after the creation of the LocalProcessHandler as that would invalidate the buffer sizes
@@ -228,16 +373,17 @@ struct LocalProcessHandler {
 
     sample_rate: SampleRate,
     buffer_size: usize,
-    input_data_callback: Option<InputDataCallback>,
-    output_data_callback: Option<OutputDataCallback>,
+    data_callback: DataCallback,
 
     // JACK audio samples are 32-bit float (unless you do some custom dark magic)
     temp_input_buffer: Vec<f32>,
     temp_output_buffer: Vec<f32>,
     playing: Arc<AtomicBool>,
+    /// Shared with the stream and the notification handler.
+    stats: Arc<StatsRecorder>,
     creation_timestamp: std::time::Instant,
-    /// This should not be called on `process`, only on `buffer_size` because it can block.
-    error_callback_ptr: ErrorCallbackPtr,
+    /// This should not be used on `process`, only on `buffer_size` because it can block.
+    error_sender: ErrorSenderPtr,
 }
 
 impl LocalProcessHandler {
@@ -247,10 +393,10 @@ impl LocalProcessHandler {
         in_ports: Vec<jack::Port<jack::AudioIn>>,
         sample_rate: SampleRate,
         buffer_size: usize,
-        input_data_callback: Option<InputDataCallback>,
-        output_data_callback: Option<OutputDataCallback>,
+        data_callback: DataCallback,
         playing: Arc<AtomicBool>,
-        error_callback_ptr: ErrorCallbackPtr,
+        stats: Arc<StatsRecorder>,
+        error_sender: ErrorSenderPtr,
     ) -> Self {
         // These may be reallocated in the `buffer_size` callback.
         let temp_input_buffer = vec![0.0; in_ports.len() * buffer_size];
@@ -261,13 +407,13 @@ impl LocalProcessHandler {
             in_ports,
             sample_rate,
             buffer_size,
-            input_data_callback,
-            output_data_callback,
+            data_callback,
             temp_input_buffer,
             temp_output_buffer,
             playing,
+            stats,
             creation_timestamp: std::time::Instant::now(),
-            error_callback_ptr,
+            error_sender,
         }
     }
 }
@@ -309,69 +455,97 @@ impl jack::ProcessHandler for LocalProcessHandler {
             ))
             .expect("`playback` occurs beyond representation supported by `StreamInstant`");
 
-        if let Some(input_callback) = &mut self.input_data_callback {
-            // Let's get the data from the input ports and run the callback
-
-            let num_in_channels = self.in_ports.len();
+        let frames_since_cycle_start = process_scope.frames_since_cycle_start() as usize;
+        let duration_since_cycle_start =
+            frames_to_duration(frames_since_cycle_start, self.sample_rate);
+        let callback = start_callback_instant
+            .add(duration_since_cycle_start)
+            .expect("`playback` occurs beyond representation supported by `StreamInstant`");
+        let capture = start_callback_instant;
+        let buffer_duration = frames_to_duration(current_frame_count, self.sample_rate);
+        let playback = start_cycle_instant
+            .add(buffer_duration)
+            .expect("`playback` occurs beyond representation supported by `StreamInstant`");
 
-            // Read the data from the input ports into the temporary buffer
-            // Go through every channel and store its data in the temporary input buffer
-            for ch_ix in 0..num_in_channels {
-                let input_channel = &self.in_ports[ch_ix].as_slice(process_scope);
-                for i in 0..current_frame_count {
-                    self.temp_input_buffer[ch_ix + i * num_in_channels] = input_channel[i];
-                }
+        // Read the data from the input ports into the temporary buffer
+        // Go through every channel and store its data in the temporary input buffer
+        let num_in_channels = self.in_ports.len();
+        for ch_ix in 0..num_in_channels {
+            let input_channel = &self.in_ports[ch_ix].as_slice(process_scope);
+            for i in 0..current_frame_count {
+                self.temp_input_buffer[ch_ix + i * num_in_channels] = input_channel[i];
             }
-            // Create a slice of exactly current_frame_count frames
-            let data = temp_buffer_to_data(
-                &mut self.temp_input_buffer,
-                current_frame_count * num_in_channels,
-            );
-            // Create timestamp
-            let frames_since_cycle_start = process_scope.frames_since_cycle_start() as usize;
-            let duration_since_cycle_start =
-                frames_to_duration(frames_since_cycle_start, self.sample_rate);
-            let callback = start_callback_instant
-                .add(duration_since_cycle_start)
-                .expect("`playback` occurs beyond representation supported by `StreamInstant`");
-            let capture = start_callback_instant;
-            let timestamp = crate::InputStreamTimestamp { callback, capture };
-            let info = crate::InputCallbackInfo { timestamp };
-            input_callback(&data, &info);
         }
 
-        if let Some(output_callback) = &mut self.output_data_callback {
-            let num_out_channels = self.out_ports.len();
-
-            // Create a slice of exactly current_frame_count frames
-            let mut data = temp_buffer_to_data(
-                &mut self.temp_output_buffer,
-                current_frame_count * num_out_channels,
-            );
-            // Create timestamp
-            let frames_since_cycle_start = process_scope.frames_since_cycle_start() as usize;
-            let duration_since_cycle_start =
-                frames_to_duration(frames_since_cycle_start, self.sample_rate);
-            let callback = start_callback_instant
-                .add(duration_since_cycle_start)
-                .expect("`playback` occurs beyond representation supported by `StreamInstant`");
-            let buffer_duration = frames_to_duration(current_frame_count, self.sample_rate);
-            let playback = start_cycle_instant
-                .add(buffer_duration)
-                .expect("`playback` occurs beyond representation supported by `StreamInstant`");
-            let timestamp = crate::OutputStreamTimestamp { callback, playback };
-            let info = crate::OutputCallbackInfo { timestamp };
-            output_callback(&mut data, &info);
-
-            // Deinterlace
-            for ch_ix in 0..num_out_channels {
-                let output_channel = &mut self.out_ports[ch_ix].as_mut_slice(process_scope);
-                for i in 0..current_frame_count {
-                    output_channel[i] = self.temp_output_buffer[ch_ix + i * num_out_channels];
-                }
+        // Create slices of exactly current_frame_count frames
+        let num_out_channels = self.out_ports.len();
+        let input = temp_buffer_to_data(
+            &mut self.temp_input_buffer,
+            current_frame_count * num_in_channels,
+        );
+        let mut output = temp_buffer_to_data(
+            &mut self.temp_output_buffer,
+            current_frame_count * num_out_channels,
+        );
+
+        // The delay includes the latency of the physical ports the stream is connected to, as
+        // `latency` does.
+        let capture_latency = self
+            .in_ports
+            .iter()
+            .map(|port| port.get_latency_range(jack::LatencyType::Capture).1)
+            .max()
+            .unwrap_or(0);
+        let capture_latency = frames_to_duration(capture_latency as usize, self.sample_rate);
+        let playback_latency = self
+            .out_ports
+            .iter()
+            .map(|port| port.get_latency_range(jack::LatencyType::Playback).1)
+            .max()
+            .unwrap_or(0);
+        let playback_latency = frames_to_duration(playback_latency as usize, self.sample_rate);
+
+        let started = Instant::now();
+        match &mut self.data_callback {
+            DataCallback::Input(input_callback) => {
+                self.stats
+                    .record_delay(capture_latency + duration_since_cycle_start);
+                let timestamp = crate::InputStreamTimestamp { callback, capture };
+                let info = crate::InputCallbackInfo { timestamp };
+                input_callback(&input, &info);
+            }
+            DataCallback::Output(output_callback) => {
+                self.stats.record_delay(buffer_duration + playback_latency);
+                let timestamp = crate::OutputStreamTimestamp { callback, playback };
+                let info = crate::OutputCallbackInfo { timestamp };
+                output_callback(&mut output, &info);
+            }
+            DataCallback::Duplex(duplex_callback) => {
+                self.stats.record_delay(
+                    capture_latency
+                        + duration_since_cycle_start
+                        + buffer_duration
+                        + playback_latency,
+                );
+                let timestamp = crate::DuplexStreamTimestamp {
+                    callback,
+                    capture,
+                    playback,
+                };
+                let info = DuplexCallbackInfo { timestamp };
+                duplex_callback(&input, &mut output, &info);
+            }
+        }
+        self.stats
+            .record_callback(current_frame_count, started.elapsed());
+
+        // Deinterlace
+        for ch_ix in 0..num_out_channels {
+            let output_channel = &mut self.out_ports[ch_ix].as_mut_slice(process_scope);
+            for i in 0..current_frame_count {
+                output_channel[i] = self.temp_output_buffer[ch_ix + i * num_out_channels];
             }
         }
-
         // Continue as normal
         jack::Control::Continue
     }
@@ -379,17 +553,18 @@ impl jack::ProcessHandler for LocalProcessHandler {
     fn buffer_size(&mut self, _: &jack::Client, size: jack::Frames) -> jack::Control {
         // The `buffer_size` callback is actually called on the process thread, but
         // it does not need to be suitable for real-time use. Thus we can simply allocate
-        // new buffers here. It is also fine to call the error callback.
+        // new buffers here. It is also fine to wait for the error sender.
         // Details: https://github.com/RustAudio/rust-jack/issues/137
         let new_size = size as usize;
         if new_size != self.buffer_size {
             self.buffer_size = new_size;
             self.temp_input_buffer = vec![0.0; self.in_ports.len() * new_size];
             self.temp_output_buffer = vec![0.0; self.out_ports.len() * new_size];
+            self.stats
+                .set_period(frames_to_duration(new_size, self.sample_rate));
             let description = format!("buffer size changed to: {}", new_size);
-            if let Ok(mut mutex_guard) = self.error_callback_ptr.lock() {
-                let err = &mut *mutex_guard;
-                err(BackendSpecificError { description }.into());
+            if let Ok(mut error_sender) = self.error_sender.lock() {
+                error_sender.send(BackendSpecificError { description }.into());
             }
         }
 
@@ -415,23 +590,43 @@ fn frames_to_duration(frames: usize, rate: crate::SampleRate) -> std::time::Dura
 /// Receives notifications from the JACK server. It is unclear if this may be run concurrent with itself under JACK2 specs
 /// so it needs to be Sync.
 struct JackNotificationHandler {
-    error_callback_ptr: ErrorCallbackPtr,
+    error_sender: ErrorSenderPtr,
     init_sample_rate_flag: Arc<AtomicBool>,
+    /// Shared with the stream, counts xruns.
+    stats: Arc<StatsRecorder>,
+    direction: StreamDirection,
+}
+
+/// Whether a stream reads from or writes to its ports, which decides how xruns are reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StreamDirection {
+    Input,
+    Output,
+    Duplex,
 }
 
 impl JackNotificationHandler {
-    pub fn new(error_callback_ptr: ErrorCallbackPtr) -> Self {
+    pub fn new(
+        error_sender: ErrorSenderPtr,
+        stats: Arc<StatsRecorder>,
+        direction: StreamDirection,
+    ) -> Self {
         JackNotificationHandler {
-            error_callback_ptr,
+            error_sender,
             init_sample_rate_flag: Arc::new(AtomicBool::new(false)),
+            stats,
+            direction,
         }
     }
 
     fn send_error(&mut self, description: String) {
+        self.send(BackendSpecificError { description }.into());
+    }
+
+    fn send(&mut self, err: StreamError) {
         // This thread isn't the audio thread, it's fine to block
-        if let Ok(mut mutex_guard) = self.error_callback_ptr.lock() {
-            let err = &mut *mutex_guard;
-            err(BackendSpecificError { description }.into());
+        if let Ok(mut error_sender) = self.error_sender.lock() {
+            error_sender.send(err);
         }
     }
 }
@@ -457,8 +652,21 @@ impl jack::NotificationHandler for JackNotificationHandler {
         }
     }
 
-    fn xrun(&mut self, _: &jack::Client) -> jack::Control {
-        self.send_error(String::from("xrun (buffer over or under run)"));
+    fn xrun(&mut self, client: &jack::Client) -> jack::Control {
+        // JACK doesn't tell how late the cycle was, so assume it was skipped entirely.
+        self.stats.record_xrun();
+        let position = self.stats.frames();
+        let lost = frames_to_duration(
+            client.buffer_size() as usize,
+            SampleRate(client.sample_rate()),
+        );
+        self.send(match self.direction {
+            StreamDirection::Input => StreamError::BufferOverrun { position, lost },
+            // A duplex stream's output is what can be heard to glitch.
+            StreamDirection::Output | StreamDirection::Duplex => {
+                StreamError::BufferUnderrun { position, lost }
+            }
+        });
         jack::Control::Continue
     }
 }
@@ -0,0 +1,71 @@
+//! Notifications of the physical ports of the JACK server changing.
+//!
+//! The devices of a JACK host are its own clients, which stay available as long as the server
+//! runs. What they connect to are the physical ports of the server, such as the ones of a sound
+//! card, so each (un)registration of a physical port is reported as a change of the device that
+//! connects to it.
+
+use crate::{BackendSpecificError, DeviceEvent, DeviceId, DeviceWatcher, WatchDevicesError};
+use std::sync::Mutex;
+
+struct WatchHandler<F> {
+    // The id and name of the device that connects to physical capture ports, and of the one that
+    // connects to physical playback ports.
+    input_device: Option<(DeviceId, String)>,
+    output_device: Option<(DeviceId, String)>,
+    // JACK requires handlers to be `Sync`, though it only calls them from one thread.
+    callback: Mutex<F>,
+}
+
+impl<F> jack::NotificationHandler for WatchHandler<F>
+where
+    F: FnMut(DeviceEvent) + Send + 'static,
+{
+    fn port_registration(&mut self, client: &jack::Client, port_id: jack::PortId, _: bool) {
+        // The port may be gone already once it is unregistered, in which case both devices may
+        // have changed.
+        let (input, output) = match client.port_by_id(port_id).map(|port| port.flags()) {
+            Some(flags) if !flags.contains(jack::PortFlags::IS_PHYSICAL) => return,
+            Some(flags) => (
+                flags.contains(jack::PortFlags::IS_OUTPUT),
+                flags.contains(jack::PortFlags::IS_INPUT),
+            ),
+            None => (true, true),
+        };
+        for (changed, device) in [(input, &self.input_device), (output, &self.output_device)] {
+            if let (true, Some((id, name))) = (changed, device) {
+                let callback = self
+                    .callback
+                    .get_mut()
+                    .unwrap_or_else(|err| err.into_inner());
+                callback(DeviceEvent::Changed {
+                    id: id.clone(),
+                    name: name.clone(),
+                });
+            }
+        }
+    }
+}
+
+pub(super) fn watch_devices<F>(
+    client: jack::Client,
+    input_device: Option<(DeviceId, String)>,
+    output_device: Option<(DeviceId, String)>,
+    callback: F,
+) -> Result<DeviceWatcher, WatchDevicesError>
+where
+    F: FnMut(DeviceEvent) + Send + 'static,
+{
+    let handler = WatchHandler {
+        input_device,
+        output_device,
+        callback: Mutex::new(callback),
+    };
+    // The client is deactivated and closed when the watcher is dropped.
+    let async_client = client
+        .activate_async(handler, ())
+        .map_err(|err| BackendSpecificError {
+            description: err.to_string(),
+        })?;
+    Ok(DeviceWatcher::new(async_client))
+}
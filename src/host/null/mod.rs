@@ -3,9 +3,9 @@ use std::time::Duration;
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
     BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError, DevicesError,
-    InputCallbackInfo, OutputCallbackInfo, PauseStreamError, PlayStreamError, SampleFormat,
-    StreamConfig, StreamError, SupportedStreamConfig, SupportedStreamConfigRange,
-    SupportedStreamConfigsError,
+    InputCallbackInfo, NegotiatedStreamConfig, OutputCallbackInfo, PauseStreamError,
+    PlayStreamError, SampleFormat, StreamConfig, StreamError, StreamStats, SupportedStreamConfig,
+    SupportedStreamConfigRange, SupportedStreamConfigsError,
 };
 
 #[derive(Default)]
@@ -132,6 +132,21 @@ impl StreamTrait for Stream {
     fn pause(&self) -> Result<(), PauseStreamError> {
         unimplemented!()
     }
+
+    fn stats(&self) -> Option<StreamStats> {
+        // The stream never runs, so nothing is ever recorded.
+        Some(StreamStats::default())
+    }
+
+    fn latency(&self) -> Option<Duration> {
+        // The stream never runs, so there is no latency to measure.
+        None
+    }
+
+    fn negotiated_config(&self) -> Option<NegotiatedStreamConfig> {
+        // Streams can't be built, so there is nothing to report.
+        None
+    }
 }
 
 impl Iterator for Devices {
@@ -0,0 +1,130 @@
+//! Collection of the [`StreamStats`] of a running stream.
+//!
+//! The audio thread records each callback in a [`StatsRecorder`] shared with the stream, which
+//! only updates atomics so that it never blocks. [`StatsRecorder::snapshot`] may be taken from any
+//! thread, and fields updated concurrently may be slightly out of step with each other.
+
+use crate::StreamStats;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The statistics of a stream, updated by its audio thread.
+#[derive(Debug)]
+pub(crate) struct StatsRecorder {
+    frames: AtomicU64,
+    callbacks: AtomicU64,
+    // Durations are stored in nanoseconds.
+    min_callback_nanos: AtomicU64,
+    max_callback_nanos: AtomicU64,
+    total_callback_nanos: AtomicU64,
+    period_nanos: AtomicU64,
+    xruns: AtomicU64,
+    delay_nanos: AtomicU64,
+}
+
+impl StatsRecorder {
+    /// A recorder for a stream that calls back every `period`.
+    pub fn new(period: Duration) -> Self {
+        StatsRecorder {
+            frames: AtomicU64::new(0),
+            callbacks: AtomicU64::new(0),
+            min_callback_nanos: AtomicU64::new(u64::MAX),
+            max_callback_nanos: AtomicU64::new(0),
+            total_callback_nanos: AtomicU64::new(0),
+            period_nanos: AtomicU64::new(nanos(period)),
+            xruns: AtomicU64::new(0),
+            delay_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a call to the data callback that took `duration` to process `frames`.
+    pub fn record_callback(&self, frames: usize, duration: Duration) {
+        let duration = nanos(duration);
+        self.frames.fetch_add(frames as u64, Ordering::Relaxed);
+        self.callbacks.fetch_add(1, Ordering::Relaxed);
+        self.min_callback_nanos
+            .fetch_min(duration, Ordering::Relaxed);
+        self.max_callback_nanos
+            .fetch_max(duration, Ordering::Relaxed);
+        self.total_callback_nanos
+            .fetch_add(duration, Ordering::Relaxed);
+    }
+
+    /// Record the delay between the device and the stream's buffer.
+    pub fn record_delay(&self, delay: Duration) {
+        self.delay_nanos.store(nanos(delay), Ordering::Relaxed);
+    }
+
+    /// Record a buffer underrun or overrun.
+    pub fn record_xrun(&self) {
+        self.xruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Change the period, e.g. after the buffer size changed.
+    #[cfg_attr(not(feature = "jack"), allow(dead_code))]
+    pub fn set_period(&self, period: Duration) {
+        self.period_nanos.store(nanos(period), Ordering::Relaxed);
+    }
+
+    /// The number of frames passed to or from the data callback so far.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// The statistics recorded so far.
+    pub fn snapshot(&self) -> StreamStats {
+        let callbacks = self.callbacks.load(Ordering::Relaxed);
+        let (min, avg) = match callbacks {
+            0 => (0, 0),
+            n => (
+                self.min_callback_nanos.load(Ordering::Relaxed),
+                self.total_callback_nanos.load(Ordering::Relaxed) / n,
+            ),
+        };
+        StreamStats {
+            frames: self.frames(),
+            callbacks,
+            min_callback_duration: Duration::from_nanos(min),
+            avg_callback_duration: Duration::from_nanos(avg),
+            max_callback_duration: Duration::from_nanos(
+                self.max_callback_nanos.load(Ordering::Relaxed),
+            ),
+            period: Duration::from_nanos(self.period_nanos.load(Ordering::Relaxed)),
+            xruns: self.xruns.load(Ordering::Relaxed),
+            delay: Duration::from_nanos(self.delay_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+// A duration in nanoseconds, saturating after about 584 years.
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+#[test]
+fn test_stats_recorder() {
+    let period = Duration::from_millis(10);
+    let stats = StatsRecorder::new(period);
+    assert_eq!(
+        stats.snapshot(),
+        StreamStats {
+            period,
+            ..StreamStats::default()
+        }
+    );
+
+    stats.record_callback(480, Duration::from_millis(1));
+    stats.record_callback(480, Duration::from_millis(5));
+    stats.record_xrun();
+    stats.record_delay(Duration::from_millis(20));
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.frames, 960);
+    assert_eq!(snapshot.callbacks, 2);
+    assert_eq!(snapshot.min_callback_duration, Duration::from_millis(1));
+    assert_eq!(snapshot.avg_callback_duration, Duration::from_millis(3));
+    assert_eq!(snapshot.max_callback_duration, Duration::from_millis(5));
+    assert_eq!(snapshot.xruns, 1);
+    assert_eq!(snapshot.delay, Duration::from_millis(20));
+    assert_eq!(snapshot.load(), 0.3);
+    assert_eq!(snapshot.peak_load(), 0.5);
+}
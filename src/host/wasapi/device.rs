@@ -443,7 +443,8 @@ impl Device {
                             &waveformat.Format as *const Audio::WAVEFORMATEX,
                         )? {
                             supported_formats.push(SupportedStreamConfigRange {
-                                channels: format.channels,
+                                min_channels: format.channels,
+                                max_channels: format.channels,
                                 min_sample_rate: sample_rate,
                                 max_sample_rate: sample_rate,
                                 buffer_size: format.buffer_size,
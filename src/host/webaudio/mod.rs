@@ -101,12 +101,14 @@ impl Device {
             max: MAX_BUFFER_SIZE,
         };
         let configs: Vec<_> = (MIN_CHANNELS..=MAX_CHANNELS)
-            .map(|channels| SupportedStreamConfigRange {
-                channels,
-                min_sample_rate: MIN_SAMPLE_RATE,
-                max_sample_rate: MAX_SAMPLE_RATE,
-                buffer_size: buffer_size.clone(),
-                sample_format: SUPPORTED_SAMPLE_FORMAT,
+            .map(|channels| {
+                SupportedStreamConfigRange::new(
+                    channels,
+                    MIN_SAMPLE_RATE,
+                    MAX_SAMPLE_RATE,
+                    buffer_size.clone(),
+                    SUPPORTED_SAMPLE_FORMAT,
+                )
             })
             .collect();
         Ok(configs.into_iter())
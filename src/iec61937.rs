@@ -0,0 +1,248 @@
+//! Helpers for sending compressed bitstreams such as AC-3 or DTS over S/PDIF or HDMI.
+//!
+//! IEC 61937 wraps each compressed frame in a *burst* which is transmitted as 16-bit stereo PCM,
+//! so that the receiver can detect and decode it. [`write_burst`] frames a payload into such a
+//! burst, and [`ChannelStatus`] describes the IEC 60958 channel status bits that tell the receiver
+//! the stream does not carry linear PCM.
+//!
+//! Hosts that support passthrough (currently ALSA) expose it through a dedicated output stream.
+
+use crate::SampleRate;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// The first sync word of a burst preamble (`Pa`).
+const SYNC_WORD_1: u16 = 0xf872;
+/// The second sync word of a burst preamble (`Pb`).
+const SYNC_WORD_2: u16 = 0x4e1f;
+/// The number of 16-bit words in a burst preamble.
+const PREAMBLE_LEN: usize = 4;
+
+/// The type of compressed data carried by a burst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DataType {
+    /// AC-3 (Dolby Digital), 1536 samples per frame.
+    Ac3,
+    /// E-AC-3 (Dolby Digital Plus), which needs four times the sample rate of the encoded audio.
+    EAc3,
+    /// DTS type I, 512 samples per frame.
+    DtsType1,
+    /// DTS type II, 1024 samples per frame.
+    DtsType2,
+    /// DTS type III, 2048 samples per frame.
+    DtsType3,
+}
+
+impl DataType {
+    /// The number of frames (pairs of 16-bit samples) one burst occupies, including padding.
+    pub fn repetition_period(self) -> usize {
+        match self {
+            DataType::Ac3 => 1536,
+            DataType::EAc3 => 6144,
+            DataType::DtsType1 => 512,
+            DataType::DtsType2 => 1024,
+            DataType::DtsType3 => 2048,
+        }
+    }
+
+    /// The number of 16-bit samples one burst occupies, including padding.
+    pub fn burst_len(self) -> usize {
+        self.repetition_period() * 2
+    }
+
+    /// The largest payload in bytes that fits into one burst.
+    pub fn max_payload_len(self) -> usize {
+        (self.burst_len() - PREAMBLE_LEN) * 2
+    }
+
+    // The data type field of the burst info (`Pc`).
+    fn code(self) -> u16 {
+        match self {
+            DataType::Ac3 => 0x01,
+            DataType::EAc3 => 0x15,
+            DataType::DtsType1 => 0x0b,
+            DataType::DtsType2 => 0x0c,
+            DataType::DtsType3 => 0x0d,
+        }
+    }
+
+    // Whether the length code (`Pd`) counts bytes rather than bits.
+    fn length_in_bytes(self) -> bool {
+        matches!(self, DataType::EAc3)
+    }
+}
+
+/// Frame `payload`, a single compressed frame of `data_type`, into a burst.
+///
+/// `burst` must hold exactly [`DataType::burst_len`] samples. The samples after the payload are
+/// zeroed. Each sample holds two payload bytes, the first one in the most significant byte.
+pub fn write_burst(
+    data_type: DataType,
+    payload: &[u8],
+    burst: &mut [i16],
+) -> Result<(), BurstError> {
+    if burst.len() != data_type.burst_len() {
+        return Err(BurstError::WrongBurstLength {
+            expected: data_type.burst_len(),
+            actual: burst.len(),
+        });
+    }
+    if payload.len() > data_type.max_payload_len() {
+        return Err(BurstError::PayloadTooLong {
+            max: data_type.max_payload_len(),
+            actual: payload.len(),
+        });
+    }
+
+    let length = if data_type.length_in_bytes() {
+        payload.len()
+    } else {
+        payload.len() * 8
+    };
+    let (preamble, data) = burst.split_at_mut(PREAMBLE_LEN);
+    preamble[0] = SYNC_WORD_1 as i16;
+    preamble[1] = SYNC_WORD_2 as i16;
+    preamble[2] = data_type.code() as i16;
+    // `max_payload_len` keeps the length within 16 bits for every data type.
+    preamble[3] = length as u16 as i16;
+
+    let mut words = payload.chunks(2);
+    for sample in data {
+        *sample = match words.next() {
+            Some(&[high, low]) => i16::from_be_bytes([high, low]),
+            Some(&[high]) => i16::from_be_bytes([high, 0]),
+            _ => 0,
+        };
+    }
+    Ok(())
+}
+
+/// An error that may occur while framing a burst with [`write_burst`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BurstError {
+    /// The output buffer does not hold exactly one burst.
+    WrongBurstLength { expected: usize, actual: usize },
+    /// The payload does not fit into one burst.
+    PayloadTooLong { max: usize, actual: usize },
+}
+
+impl Display for BurstError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongBurstLength { expected, actual } => write!(
+                f,
+                "a burst holds {} samples, but the buffer holds {}",
+                expected, actual
+            ),
+            Self::PayloadTooLong { max, actual } => write!(
+                f,
+                "the payload is {} bytes long, but a burst holds at most {}",
+                actual, max
+            ),
+        }
+    }
+}
+
+impl Error for BurstError {}
+
+/// The first four bytes of the IEC 60958 consumer channel status.
+///
+/// These correspond to the `AES0` to `AES3` arguments of ALSA's `iec958` and `hdmi` PCMs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelStatus(pub [u8; 4]);
+
+impl ChannelStatus {
+    const AES0_NON_AUDIO: u8 = 0x02;
+    const AES0_NOT_COPYRIGHT: u8 = 0x04;
+    const AES1_PCM_CODER: u8 = 0x02;
+    const AES1_ORIGINAL: u8 = 0x80;
+
+    /// The channel status for linear PCM at `sample_rate`.
+    pub fn pcm(sample_rate: SampleRate) -> Self {
+        ChannelStatus([
+            Self::AES0_NOT_COPYRIGHT,
+            Self::AES1_ORIGINAL | Self::AES1_PCM_CODER,
+            0,
+            sample_rate_code(sample_rate),
+        ])
+    }
+
+    /// The channel status for an IEC 61937 bitstream at `sample_rate`.
+    pub fn non_audio(sample_rate: SampleRate) -> Self {
+        let mut status = Self::pcm(sample_rate);
+        status.set_non_audio(true);
+        status
+    }
+
+    /// Whether the stream is marked as not carrying linear PCM.
+    pub fn is_non_audio(&self) -> bool {
+        self.0[0] & Self::AES0_NON_AUDIO != 0
+    }
+
+    /// Mark the stream as carrying, or not carrying, linear PCM.
+    pub fn set_non_audio(&mut self, non_audio: bool) {
+        if non_audio {
+            self.0[0] |= Self::AES0_NON_AUDIO;
+        } else {
+            self.0[0] &= !Self::AES0_NON_AUDIO;
+        }
+    }
+}
+
+// The sampling frequency field of the consumer channel status (`AES3`).
+fn sample_rate_code(sample_rate: SampleRate) -> u8 {
+    match sample_rate.0 {
+        22_050 => 0x04,
+        24_000 => 0x06,
+        32_000 => 0x03,
+        44_100 => 0x00,
+        48_000 => 0x02,
+        88_200 => 0x08,
+        96_000 => 0x0a,
+        176_400 => 0x0c,
+        192_000 => 0x0e,
+        768_000 => 0x09,
+        // "Not indicated".
+        _ => 0x01,
+    }
+}
+
+#[test]
+fn test_write_burst() {
+    let mut burst = vec![1i16; DataType::Ac3.burst_len()];
+    write_burst(DataType::Ac3, &[0x0b, 0x77, 0x12], &mut burst).unwrap();
+    assert_eq!(
+        burst[..6],
+        [0xf872u16 as i16, 0x4e1f, 0x0001, 24, 0x0b77, 0x1200]
+    );
+    assert!(burst[6..].iter().all(|&sample| sample == 0));
+
+    let mut burst = vec![0i16; DataType::EAc3.burst_len()];
+    write_burst(DataType::EAc3, &[0; 100], &mut burst).unwrap();
+    assert_eq!(burst[2..4], [0x0015, 100]);
+
+    let max = DataType::DtsType1.max_payload_len();
+    assert_eq!(
+        write_burst(DataType::DtsType1, &vec![0; max + 1], &mut [0; 1024]),
+        Err(BurstError::PayloadTooLong {
+            max,
+            actual: max + 1
+        })
+    );
+    assert_eq!(
+        write_burst(DataType::DtsType1, &[], &mut [0; 1023]),
+        Err(BurstError::WrongBurstLength {
+            expected: 1024,
+            actual: 1023
+        })
+    );
+}
+
+#[test]
+fn test_channel_status() {
+    let status = ChannelStatus::non_audio(SampleRate(48_000));
+    assert_eq!(status.0, [0x06, 0x82, 0x00, 0x02]);
+    assert!(status.is_non_audio());
+    assert!(!ChannelStatus::pcm(SampleRate(44_100)).is_non_audio());
+}
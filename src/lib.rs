@@ -169,7 +169,10 @@ pub use platform::{
     available_hosts, default_host, host_from_id, Device, Devices, Host, HostId, Stream,
     SupportedInputConfigs, SupportedOutputConfigs, ALL_HOSTS,
 };
-pub use samples_formats::{FromSample, Sample, SampleFormat, SizedSample, I24, I48, U24, U48};
+pub use samples_formats::{
+    pack_i24, unpack_i24, ALaw, FromSample, MuLaw, Sample, SampleFormat, SizedSample, I24, I48,
+    U24, U48,
+};
 use std::convert::TryInto;
 use std::ops::{Div, Mul};
 use std::time::Duration;
@@ -178,6 +181,7 @@ use wasm_bindgen::prelude::*;
 
 mod error;
 mod host;
+pub mod iec61937;
 pub mod platform;
 mod samples_formats;
 pub mod traits;
@@ -265,6 +269,18 @@ pub struct StreamConfig {
     pub buffer_size: BufferSize,
 }
 
+/// The set of parameters used to open a duplex stream, which captures and plays audio through the
+/// same data callback.
+///
+/// Both directions share the sample rate and buffer size, but may have different channel counts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DuplexStreamConfig {
+    pub input_channels: ChannelCount,
+    pub output_channels: ChannelCount,
+    pub sample_rate: SampleRate,
+    pub buffer_size: BufferSize,
+}
+
 /// Describes the minimum and maximum supported buffer size for the device
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum SupportedBufferSize {
@@ -281,7 +297,10 @@ pub enum SupportedBufferSize {
 /// [`Device::supported_input/output_configs`](traits::DeviceTrait#required-methods) method.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SupportedStreamConfigRange {
-    pub(crate) channels: ChannelCount,
+    /// Minimum number of channels of the supported formats.
+    pub(crate) min_channels: ChannelCount,
+    /// Maximum number of channels of the supported formats.
+    pub(crate) max_channels: ChannelCount,
     /// Minimum value for the samples rate of the supported formats.
     pub(crate) min_sample_rate: SampleRate,
     /// Maximum value for the samples rate of the supported formats.
@@ -292,6 +311,35 @@ pub struct SupportedStreamConfigRange {
     pub(crate) sample_format: SampleFormat,
 }
 
+/// Describes a set of supported stream configurations with a range of channel counts and a set of
+/// sample rates, retrieved via the
+/// [`Device::supported_input/output_config_sets`](traits::DeviceTrait#provided-methods) method.
+///
+/// A single set describes as many configurations as a whole list of
+/// [`SupportedStreamConfigRange`]s for devices that only support discrete sample rates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupportedStreamConfigSet {
+    /// Minimum number of channels of the supported formats.
+    pub(crate) min_channels: ChannelCount,
+    /// Maximum number of channels of the supported formats.
+    pub(crate) max_channels: ChannelCount,
+    /// Sample rates of the supported formats.
+    pub(crate) sample_rates: SupportedSampleRates,
+    /// Buffersize ranges supported by the device
+    pub(crate) buffer_size: SupportedBufferSize,
+    /// Type of data expected by the device.
+    pub(crate) sample_format: SampleFormat,
+}
+
+/// Describes the sample rates of a [`SupportedStreamConfigSet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupportedSampleRates {
+    /// Every sample rate from `min` to `max`.
+    Range { min: SampleRate, max: SampleRate },
+    /// Only the listed sample rates, in ascending order.
+    Discrete(Vec<SampleRate>),
+}
+
 /// Describes a single supported stream configuration, retrieved via either a
 /// [`SupportedStreamConfigRange`] instance or one of the
 /// [`Device::default_input/output_config`](traits::DeviceTrait#required-methods) methods.
@@ -361,6 +409,17 @@ pub struct OutputStreamTimestamp {
     pub playback: StreamInstant,
 }
 
+/// A timestamp associated with a call to a duplex stream's data callback.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DuplexStreamTimestamp {
+    /// The instant the stream's data callback was invoked.
+    pub callback: StreamInstant,
+    /// The instant that the input data was captured from the device.
+    pub capture: StreamInstant,
+    /// The predicted instant that the output data will be delivered to the device for playback.
+    pub playback: StreamInstant,
+}
+
 /// Information relevant to a single call to the user's input stream data callback.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InputCallbackInfo {
@@ -374,6 +433,89 @@ pub struct OutputCallbackInfo {
     timestamp: OutputStreamTimestamp,
 }
 
+/// A snapshot of the statistics of a running stream, as returned by
+/// [`StreamTrait::stats`](traits::StreamTrait::stats).
+///
+/// The statistics cover the whole lifetime of the stream. Callback durations measure the time
+/// spent in the data callback, which should stay well below the `period` at which the host calls
+/// it to avoid xruns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StreamStats {
+    /// The number of frames passed to or from the data callback.
+    pub frames: u64,
+    /// The number of calls to the data callback.
+    pub callbacks: u64,
+    /// The shortest time spent in the data callback.
+    pub min_callback_duration: Duration,
+    /// The average time spent in the data callback.
+    pub avg_callback_duration: Duration,
+    /// The longest time spent in the data callback.
+    pub max_callback_duration: Duration,
+    /// The time between calls to the data callback, i.e. the duration of one period of audio.
+    pub period: Duration,
+    /// The number of buffer underruns or overruns.
+    pub xruns: u64,
+    /// The delay between the device and the stream's buffer when it was last measured.
+    pub delay: Duration,
+}
+
+/// The configuration a stream runs with, as negotiated with the device when it was built. See
+/// [`StreamTrait::negotiated_config`](traits::StreamTrait::negotiated_config).
+///
+/// Unlike the requested [`StreamConfig`], the `buffer_size` of `config` is always
+/// [`BufferSize::Fixed`], holding the number of frames buffered between the stream and the device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NegotiatedStreamConfig {
+    /// The channel count, sample rate and buffer size of the stream. For duplex streams, the
+    /// channel count is that of the output.
+    pub config: StreamConfig,
+    /// The channel count of the input of duplex streams, or `None` for other streams.
+    pub input_channels: Option<ChannelCount>,
+    /// The sample format of the data passed to or from the data callback.
+    pub sample_format: SampleFormat,
+    /// The number of frames in one period of the device, which is how many the host usually passes
+    /// to or from each call of the data callback.
+    pub period_size: FrameCount,
+}
+
+/// A stable identifier of a device, see [`DeviceTrait::id`](traits::DeviceTrait::id).
+///
+/// Unlike the name, it tells apart identical devices and keeps identifying the same device after
+/// it is reconnected, at least as far as the host allows. It can be saved as a string with
+/// `to_string` and restored with `parse`, to find the device again with
+/// [`HostTrait::device_by_id`](traits::HostTrait::device_by_id) later on.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceId(String);
+
+/// A change to the devices of a host, as passed to the callback of
+/// [`HostTrait::watch_devices`](traits::HostTrait::watch_devices).
+///
+/// Devices are identified by their [`id`](traits::DeviceTrait::id), and carry their
+/// [`name`](traits::DeviceTrait::name) for display.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceEvent {
+    /// A device was added, e.g. because it was plugged in.
+    Added { id: DeviceId, name: String },
+    /// A device was removed, e.g. because it was unplugged. Streams built from it stop with an
+    /// error.
+    Removed { id: DeviceId, name: String },
+    /// The device is still available, but what it is connected to changed, e.g. because the
+    /// physical ports of a JACK server were registered or unregistered.
+    Changed { id: DeviceId, name: String },
+}
+
+/// Watches the devices of a host until it is dropped, see
+/// [`HostTrait::watch_devices`](traits::HostTrait::watch_devices).
+pub struct DeviceWatcher {
+    _watch: Box<dyn Send>,
+}
+
+/// Information relevant to a single call to the user's duplex stream data callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplexCallbackInfo {
+    timestamp: DuplexStreamTimestamp,
+}
+
 impl SupportedStreamConfig {
     pub fn new(
         channels: ChannelCount,
@@ -507,6 +649,83 @@ impl OutputCallbackInfo {
     }
 }
 
+impl DuplexCallbackInfo {
+    pub fn new(timestamp: DuplexStreamTimestamp) -> Self {
+        Self { timestamp }
+    }
+
+    /// The timestamp associated with the call to a duplex stream's data callback.
+    pub fn timestamp(&self) -> DuplexStreamTimestamp {
+        self.timestamp
+    }
+}
+
+impl StreamStats {
+    /// The average time spent in the data callback, as a fraction of the period.
+    ///
+    /// Returns `0.0` if the period is unknown.
+    pub fn load(&self) -> f64 {
+        self.fraction_of_period(self.avg_callback_duration)
+    }
+
+    /// The longest time spent in the data callback, as a fraction of the period.
+    ///
+    /// A value close to or above `1.0` means the callback risked missing its deadline.
+    pub fn peak_load(&self) -> f64 {
+        self.fraction_of_period(self.max_callback_duration)
+    }
+
+    fn fraction_of_period(&self, duration: Duration) -> f64 {
+        if self.period.is_zero() {
+            return 0.0;
+        }
+        duration.as_secs_f64() / self.period.as_secs_f64()
+    }
+}
+
+impl DeviceWatcher {
+    // Internal constructor for host implementations, which stop watching when `watch` is dropped.
+    #[allow(dead_code)]
+    pub(crate) fn new(watch: impl Send + 'static) -> Self {
+        DeviceWatcher {
+            _watch: Box::new(watch),
+        }
+    }
+}
+
+impl DeviceId {
+    /// The identifier as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for DeviceId {
+    fn from(id: String) -> Self {
+        DeviceId(id)
+    }
+}
+
+impl std::fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::str::FromStr for DeviceId {
+    type Err = std::convert::Infallible;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        Ok(DeviceId(id.to_owned()))
+    }
+}
+
+impl std::fmt::Debug for DeviceWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceWatcher").finish_non_exhaustive()
+    }
+}
+
 #[allow(clippy::len_without_is_empty)]
 impl Data {
     // Internal constructor for host implementations to use.
@@ -599,6 +818,61 @@ impl Data {
             None
         }
     }
+
+    /// Access the data as packed 24-bit samples, each three bytes in native byte order.
+    ///
+    /// Returns `None` if the sample format is not [`SampleFormat::I24Packed`].
+    pub fn as_packed_i24(&self) -> Option<&[[u8; 3]]> {
+        if self.sample_format == SampleFormat::I24Packed {
+            // The safety of this block relies on correct construction of the `Data` instance. See
+            // the unsafe `from_parts` constructor for these requirements.
+            unsafe {
+                Some(std::slice::from_raw_parts(
+                    self.data as *const [u8; 3],
+                    self.len,
+                ))
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Access the data as packed 24-bit samples, each three bytes in native byte order.
+    ///
+    /// Returns `None` if the sample format is not [`SampleFormat::I24Packed`].
+    pub fn as_packed_i24_mut(&mut self) -> Option<&mut [[u8; 3]]> {
+        if self.sample_format == SampleFormat::I24Packed {
+            // The safety of this block relies on correct construction of the `Data` instance. See
+            // the unsafe `from_parts` constructor for these requirements.
+            unsafe {
+                Some(std::slice::from_raw_parts_mut(
+                    self.data as *mut [u8; 3],
+                    self.len,
+                ))
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over the frames of packed 24-bit samples, each holding `channels` samples.
+    ///
+    /// Returns `None` if the sample format is not [`SampleFormat::I24Packed`].
+    pub fn packed_i24_frames(
+        &self,
+        channels: ChannelCount,
+    ) -> Option<std::slice::ChunksExact<'_, [u8; 3]>> {
+        self.as_packed_i24()
+            .map(|samples| samples.chunks_exact(channels as usize))
+    }
+
+    /// Iterate over the packed 24-bit samples, widened to [`I24`].
+    ///
+    /// Returns `None` if the sample format is not [`SampleFormat::I24Packed`].
+    pub fn packed_i24_samples(&self) -> Option<impl Iterator<Item = I24> + '_> {
+        self.as_packed_i24()
+            .map(|samples| samples.iter().map(|&sample| unpack_i24(sample)))
+    }
 }
 
 impl SupportedStreamConfigRange {
@@ -610,7 +884,27 @@ impl SupportedStreamConfigRange {
         sample_format: SampleFormat,
     ) -> Self {
         Self {
-            channels,
+            min_channels: channels,
+            max_channels: channels,
+            min_sample_rate,
+            max_sample_rate,
+            buffer_size,
+            sample_format,
+        }
+    }
+
+    /// Create a range that supports every channel count from `min_channels` to `max_channels`.
+    pub fn with_channel_range(
+        min_channels: ChannelCount,
+        max_channels: ChannelCount,
+        min_sample_rate: SampleRate,
+        max_sample_rate: SampleRate,
+        buffer_size: SupportedBufferSize,
+        sample_format: SampleFormat,
+    ) -> Self {
+        Self {
+            min_channels,
+            max_channels,
             min_sample_rate,
             max_sample_rate,
             buffer_size,
@@ -618,8 +912,48 @@ impl SupportedStreamConfigRange {
         }
     }
 
+    /// The number of channels of the configs retrieved via
+    /// [`with_sample_rate`](Self::with_sample_rate) and friends.
+    ///
+    /// This is stereo or mono if the range supports them, and the maximum number of channels
+    /// otherwise. See [`min_channels`](Self::min_channels) and
+    /// [`max_channels`](Self::max_channels) for the full range.
     pub fn channels(&self) -> ChannelCount {
-        self.channels
+        if self.supports_channels(2) {
+            2
+        } else if self.supports_channels(1) {
+            1
+        } else {
+            self.max_channels
+        }
+    }
+
+    pub fn min_channels(&self) -> ChannelCount {
+        self.min_channels
+    }
+
+    pub fn max_channels(&self) -> ChannelCount {
+        self.max_channels
+    }
+
+    /// Whether `channels` is within the range of supported channel counts.
+    pub fn supports_channels(&self, channels: ChannelCount) -> bool {
+        self.min_channels <= channels && channels <= self.max_channels
+    }
+
+    /// Narrow this range down to a single channel count.
+    ///
+    /// Returns `None` if `channels` is outside the range of supported channel counts.
+    pub fn try_with_channels(self, channels: ChannelCount) -> Option<Self> {
+        if self.supports_channels(channels) {
+            Some(Self {
+                min_channels: channels,
+                max_channels: channels,
+                ..self
+            })
+        } else {
+            None
+        }
     }
 
     pub fn min_sample_rate(&self) -> SampleRate {
@@ -657,7 +991,7 @@ impl SupportedStreamConfigRange {
     pub fn try_with_sample_rate(self, sample_rate: SampleRate) -> Option<SupportedStreamConfig> {
         if self.min_sample_rate <= sample_rate && sample_rate <= self.max_sample_rate {
             Some(SupportedStreamConfig {
-                channels: self.channels,
+                channels: self.channels(),
                 sample_rate,
                 sample_format: self.sample_format,
                 buffer_size: self.buffer_size,
@@ -671,7 +1005,7 @@ impl SupportedStreamConfigRange {
     #[inline]
     pub fn with_max_sample_rate(self) -> SupportedStreamConfig {
         SupportedStreamConfig {
-            channels: self.channels,
+            channels: self.channels(),
             sample_rate: self.max_sample_rate,
             sample_format: self.sample_format,
             buffer_size: self.buffer_size,
@@ -703,49 +1037,263 @@ impl SupportedStreamConfigRange {
     /// - 44100 (cd quality)
     /// - Max sample rate
     pub fn cmp_default_heuristics(&self, other: &Self) -> std::cmp::Ordering {
-        use std::cmp::Ordering::Equal;
-        use SampleFormat::{F32, I16, U16};
+        self.heuristics_key().cmp(&other.heuristics_key())
+    }
 
-        let cmp_stereo = (self.channels == 2).cmp(&(other.channels == 2));
-        if cmp_stereo != Equal {
-            return cmp_stereo;
+    fn heuristics_key(&self) -> HeuristicsKey {
+        HeuristicsKey {
+            channels: self.channels(),
+            sample_format: self.sample_format,
+            supports_44100: self.min_sample_rate <= HZ_44100 && HZ_44100 <= self.max_sample_rate,
+            max_sample_rate: self.max_sample_rate,
         }
+    }
+}
 
-        let cmp_mono = (self.channels == 1).cmp(&(other.channels == 1));
-        if cmp_mono != Equal {
-            return cmp_mono;
+impl From<SupportedStreamConfigRange> for SupportedStreamConfigSet {
+    fn from(range: SupportedStreamConfigRange) -> Self {
+        SupportedStreamConfigSet {
+            min_channels: range.min_channels,
+            max_channels: range.max_channels,
+            sample_rates: SupportedSampleRates::Range {
+                min: range.min_sample_rate,
+                max: range.max_sample_rate,
+            },
+            buffer_size: range.buffer_size,
+            sample_format: range.sample_format,
         }
+    }
+}
 
-        let cmp_channels = self.channels.cmp(&other.channels);
-        if cmp_channels != Equal {
-            return cmp_channels;
+impl SupportedSampleRates {
+    /// The lowest supported sample rate, or `None` if there is no supported sample rate.
+    pub fn min(&self) -> Option<SampleRate> {
+        match self {
+            Self::Range { min, .. } => Some(*min),
+            Self::Discrete(rates) => rates.first().copied(),
         }
+    }
 
-        let cmp_f32 = (self.sample_format == F32).cmp(&(other.sample_format == F32));
-        if cmp_f32 != Equal {
-            return cmp_f32;
+    /// The highest supported sample rate, or `None` if there is no supported sample rate.
+    pub fn max(&self) -> Option<SampleRate> {
+        match self {
+            Self::Range { max, .. } => Some(*max),
+            Self::Discrete(rates) => rates.last().copied(),
         }
+    }
 
-        let cmp_i16 = (self.sample_format == I16).cmp(&(other.sample_format == I16));
-        if cmp_i16 != Equal {
-            return cmp_i16;
+    /// Whether `sample_rate` is supported.
+    pub fn contains(&self, sample_rate: SampleRate) -> bool {
+        match self {
+            Self::Range { min, max } => *min <= sample_rate && sample_rate <= *max,
+            Self::Discrete(rates) => rates.binary_search(&sample_rate).is_ok(),
         }
+    }
 
-        let cmp_u16 = (self.sample_format == U16).cmp(&(other.sample_format == U16));
-        if cmp_u16 != Equal {
-            return cmp_u16;
+    /// An iterator yielding the supported sample rates as `(min, max)` ranges.
+    ///
+    /// Each discrete sample rate is yielded as a range of its own.
+    pub fn ranges(&self) -> impl Iterator<Item = (SampleRate, SampleRate)> + '_ {
+        let (range, discrete) = match self {
+            Self::Range { min, max } => (Some((*min, *max)), &[][..]),
+            Self::Discrete(rates) => (None, &rates[..]),
+        };
+        range
+            .into_iter()
+            .chain(discrete.iter().map(|&rate| (rate, rate)))
+    }
+}
+
+impl SupportedStreamConfigSet {
+    pub fn new(
+        min_channels: ChannelCount,
+        max_channels: ChannelCount,
+        sample_rates: SupportedSampleRates,
+        buffer_size: SupportedBufferSize,
+        sample_format: SampleFormat,
+    ) -> Self {
+        Self {
+            min_channels,
+            max_channels,
+            sample_rates,
+            buffer_size,
+            sample_format,
         }
+    }
 
-        const HZ_44100: SampleRate = SampleRate(44_100);
-        let r44100_in_self = self.min_sample_rate <= HZ_44100 && HZ_44100 <= self.max_sample_rate;
-        let r44100_in_other =
-            other.min_sample_rate <= HZ_44100 && HZ_44100 <= other.max_sample_rate;
-        let cmp_r44100 = r44100_in_self.cmp(&r44100_in_other);
-        if cmp_r44100 != Equal {
-            return cmp_r44100;
+    /// Group `ranges` into as few sets as possible.
+    ///
+    /// Ranges with a single sample rate that only differ in it are merged into a set with
+    /// [`SupportedSampleRates::Discrete`] rates. Every other range becomes a set of its own.
+    pub fn from_ranges(ranges: impl IntoIterator<Item = SupportedStreamConfigRange>) -> Vec<Self> {
+        let mut sets: Vec<Self> = Vec::new();
+        for range in ranges {
+            if range.min_sample_rate == range.max_sample_rate {
+                let rate = range.min_sample_rate;
+                let same_set = sets.iter_mut().find(|set| {
+                    set.min_channels == range.min_channels
+                        && set.max_channels == range.max_channels
+                        && set.buffer_size == range.buffer_size
+                        && set.sample_format == range.sample_format
+                        && matches!(set.sample_rates, SupportedSampleRates::Discrete(_))
+                });
+                if let Some(SupportedStreamConfigSet {
+                    sample_rates: SupportedSampleRates::Discrete(rates),
+                    ..
+                }) = same_set
+                {
+                    if let Err(index) = rates.binary_search(&rate) {
+                        rates.insert(index, rate);
+                    }
+                    continue;
+                }
+                sets.push(Self {
+                    sample_rates: SupportedSampleRates::Discrete(vec![rate]),
+                    ..range.into()
+                });
+            } else {
+                sets.push(range.into());
+            }
         }
+        sets
+    }
 
-        self.max_sample_rate.cmp(&other.max_sample_rate)
+    /// The number of channels of the configs retrieved via
+    /// [`with_sample_rate`](Self::with_sample_rate) and friends.
+    ///
+    /// See [`SupportedStreamConfigRange::channels`].
+    pub fn channels(&self) -> ChannelCount {
+        self.as_range(SampleRate(0)).channels()
+    }
+
+    pub fn min_channels(&self) -> ChannelCount {
+        self.min_channels
+    }
+
+    pub fn max_channels(&self) -> ChannelCount {
+        self.max_channels
+    }
+
+    /// Whether `channels` is within the range of supported channel counts.
+    pub fn supports_channels(&self, channels: ChannelCount) -> bool {
+        self.min_channels <= channels && channels <= self.max_channels
+    }
+
+    pub fn sample_rates(&self) -> &SupportedSampleRates {
+        &self.sample_rates
+    }
+
+    pub fn buffer_size(&self) -> &SupportedBufferSize {
+        &self.buffer_size
+    }
+
+    pub fn sample_format(&self) -> SampleFormat {
+        self.sample_format
+    }
+
+    /// An iterator yielding this set as [`SupportedStreamConfigRange`]s, one per range of sample
+    /// rates.
+    pub fn ranges(&self) -> impl Iterator<Item = SupportedStreamConfigRange> + '_ {
+        self.sample_rates
+            .ranges()
+            .map(move |(min, max)| SupportedStreamConfigRange {
+                min_sample_rate: min,
+                max_sample_rate: max,
+                ..self.as_range(min)
+            })
+    }
+
+    /// Retrieve a [`SupportedStreamConfig`] with the given sample rate and buffer size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the given `sample_rate` is not within this [`SupportedStreamConfigSet`]. For a
+    /// non-panicking variant, use [`try_with_sample_rate`](#method.try_with_sample_rate).
+    pub fn with_sample_rate(&self, sample_rate: SampleRate) -> SupportedStreamConfig {
+        self.try_with_sample_rate(sample_rate)
+            .expect("sample rate out of range")
+    }
+
+    /// Retrieve a [`SupportedStreamConfig`] with the given sample rate and buffer size.
+    ///
+    /// Returns `None` if the given sample rate is not within this [`SupportedStreamConfigSet`].
+    pub fn try_with_sample_rate(&self, sample_rate: SampleRate) -> Option<SupportedStreamConfig> {
+        if self.sample_rates.contains(sample_rate) {
+            self.as_range(sample_rate).try_with_sample_rate(sample_rate)
+        } else {
+            None
+        }
+    }
+
+    /// Turns this [`SupportedStreamConfigSet`] into a [`SupportedStreamConfig`] corresponding to
+    /// the maximum sample rate.
+    ///
+    /// Returns `None` if there is no supported sample rate.
+    pub fn with_max_sample_rate(&self) -> Option<SupportedStreamConfig> {
+        self.try_with_sample_rate(self.sample_rates.max()?)
+    }
+
+    /// A comparison function which compares two [`SupportedStreamConfigSet`]s in terms of their
+    /// priority of use as a default stream format.
+    ///
+    /// The heuristics are the same as those of
+    /// [`SupportedStreamConfigRange::cmp_default_heuristics`].
+    pub fn cmp_default_heuristics(&self, other: &Self) -> std::cmp::Ordering {
+        self.heuristics_key().cmp(&other.heuristics_key())
+    }
+
+    fn heuristics_key(&self) -> HeuristicsKey {
+        HeuristicsKey {
+            channels: self.channels(),
+            sample_format: self.sample_format,
+            supports_44100: self.sample_rates.contains(HZ_44100),
+            max_sample_rate: self.sample_rates.max().unwrap_or(SampleRate(0)),
+        }
+    }
+
+    // A range with this set's channels, buffer size and format that only holds `sample_rate`.
+    fn as_range(&self, sample_rate: SampleRate) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::with_channel_range(
+            self.min_channels,
+            self.max_channels,
+            sample_rate,
+            sample_rate,
+            self.buffer_size,
+            self.sample_format,
+        )
+    }
+}
+
+const HZ_44100: SampleRate = SampleRate(44_100);
+
+// The properties of a range or set of configs that the default heuristics look at.
+#[derive(PartialEq, Eq)]
+struct HeuristicsKey {
+    channels: ChannelCount,
+    sample_format: SampleFormat,
+    supports_44100: bool,
+    max_sample_rate: SampleRate,
+}
+
+impl PartialOrd for HeuristicsKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeuristicsKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use SampleFormat::{F32, I16, U16};
+
+        (self.channels == 2)
+            .cmp(&(other.channels == 2))
+            .then((self.channels == 1).cmp(&(other.channels == 1)))
+            .then(self.channels.cmp(&other.channels))
+            .then((self.sample_format == F32).cmp(&(other.sample_format == F32)))
+            .then((self.sample_format == I16).cmp(&(other.sample_format == I16)))
+            .then((self.sample_format == U16).cmp(&(other.sample_format == U16)))
+            .then(self.supports_44100.cmp(&other.supports_44100))
+            .then(self.max_sample_rate.cmp(&other.max_sample_rate))
     }
 }
 
@@ -754,35 +1302,40 @@ fn test_cmp_default_heuristics() {
     let mut formats = [
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 2,
+            min_channels: 2,
+            max_channels: 2,
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(96000),
             sample_format: SampleFormat::F32,
         },
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 1,
+            min_channels: 1,
+            max_channels: 1,
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(96000),
             sample_format: SampleFormat::F32,
         },
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 2,
+            min_channels: 2,
+            max_channels: 2,
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(96000),
             sample_format: SampleFormat::I16,
         },
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 2,
+            min_channels: 2,
+            max_channels: 2,
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(96000),
             sample_format: SampleFormat::U16,
         },
         SupportedStreamConfigRange {
             buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 2,
+            min_channels: 2,
+            max_channels: 2,
             min_sample_rate: SampleRate(1),
             max_sample_rate: SampleRate(22050),
             sample_format: SampleFormat::F32,
@@ -818,12 +1371,105 @@ fn test_cmp_default_heuristics() {
     assert_eq!(formats[4].channels(), 2);
 }
 
+#[test]
+fn test_channel_range() {
+    let range = SupportedStreamConfigRange::with_channel_range(
+        1,
+        64,
+        SampleRate(48000),
+        SampleRate(48000),
+        SupportedBufferSize::Unknown,
+        SampleFormat::F32,
+    );
+    assert_eq!(range.channels(), 2);
+    assert_eq!(range.with_max_sample_rate().channels(), 2);
+    assert_eq!(range.try_with_channels(64).unwrap().channels(), 64);
+    assert_eq!(range.try_with_channels(65), None);
+
+    let range = SupportedStreamConfigRange {
+        min_channels: 8,
+        ..range
+    };
+    assert_eq!(range.channels(), 64);
+}
+
+#[test]
+fn test_config_set() {
+    let range = |rate| {
+        SupportedStreamConfigRange::with_channel_range(
+            1,
+            8,
+            SampleRate(rate),
+            SampleRate(rate),
+            SupportedBufferSize::Unknown,
+            SampleFormat::I16,
+        )
+    };
+    let wide = SupportedStreamConfigRange::new(
+        2,
+        SampleRate(8000),
+        SampleRate(32000),
+        SupportedBufferSize::Unknown,
+        SampleFormat::F32,
+    );
+    let sets = SupportedStreamConfigSet::from_ranges(vec![range(96000), wide, range(44100)]);
+    assert_eq!(sets.len(), 2);
+    assert_eq!(
+        sets[0].sample_rates(),
+        &SupportedSampleRates::Discrete(vec![SampleRate(44100), SampleRate(96000)])
+    );
+    assert_eq!(
+        sets[0].ranges().collect::<Vec<_>>(),
+        [range(44100), range(96000)]
+    );
+    assert_eq!(sets[1].ranges().collect::<Vec<_>>(), [wide]);
+
+    assert_eq!(sets[0].try_with_sample_rate(SampleRate(48000)), None);
+    assert_eq!(
+        sets[0].with_max_sample_rate(),
+        Some(range(96000).with_max_sample_rate())
+    );
+    assert_eq!(
+        sets[1].try_with_sample_rate(SampleRate(16000)),
+        wide.try_with_sample_rate(SampleRate(16000))
+    );
+
+    assert_eq!(
+        sets[0].cmp_default_heuristics(&sets[1]),
+        range(44100).cmp_default_heuristics(&wide)
+    );
+    assert_eq!(
+        sets[0].cmp_default_heuristics(&SupportedStreamConfigSet::from(range(96000))),
+        std::cmp::Ordering::Greater
+    );
+}
+
 impl From<SupportedStreamConfig> for StreamConfig {
     fn from(conf: SupportedStreamConfig) -> Self {
         conf.config()
     }
 }
 
+impl DuplexStreamConfig {
+    /// The config of the input side of the stream.
+    pub fn input(&self) -> StreamConfig {
+        StreamConfig {
+            channels: self.input_channels,
+            sample_rate: self.sample_rate,
+            buffer_size: self.buffer_size,
+        }
+    }
+
+    /// The config of the output side of the stream.
+    pub fn output(&self) -> StreamConfig {
+        StreamConfig {
+            channels: self.output_channels,
+            sample_rate: self.sample_rate,
+            buffer_size: self.buffer_size,
+        }
+    }
+}
+
 // If a backend does not provide an API for retrieving supported formats, we query it with a bunch
 // of commonly used rates. This is always the case for wasapi and is sometimes the case for alsa.
 //
@@ -879,3 +1525,20 @@ fn test_stream_instant() {
     );
     assert_eq!(max.add(Duration::from_secs(1)), None);
 }
+
+#[test]
+fn test_packed_i24_accessors() {
+    let mut samples = [
+        pack_i24(I24::new_unchecked(-2)),
+        pack_i24(I24::new_unchecked(0x12_3456)),
+    ];
+    let data =
+        unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 2, SampleFormat::I24Packed) };
+    assert_eq!(data.bytes().len(), 6);
+    assert!(data.as_slice::<I24>().is_none());
+    assert_eq!(
+        data.packed_i24_samples().unwrap().collect::<Vec<_>>(),
+        [I24::new_unchecked(-2), I24::new_unchecked(0x12_3456)]
+    );
+    assert_eq!(data.packed_i24_frames(2).unwrap().len(), 1);
+}
@@ -314,6 +314,15 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            fn id(&self) -> Result<crate::DeviceId, crate::DeviceNameError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.id(),
+                    )*
+                }
+            }
+
             fn supports_input(&self) -> bool {
                 match self.0 {
                     $(
@@ -358,6 +367,24 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            fn supported_input_config_sets(&self) -> Result<Vec<crate::SupportedStreamConfigSet>, crate::SupportedStreamConfigsError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.supported_input_config_sets(),
+                    )*
+                }
+            }
+
+            fn supported_output_config_sets(&self) -> Result<Vec<crate::SupportedStreamConfigSet>, crate::SupportedStreamConfigsError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.supported_output_config_sets(),
+                    )*
+                }
+            }
+
             fn default_input_config(&self) -> Result<crate::SupportedStreamConfig, crate::DefaultStreamConfigError> {
                 match self.0 {
                     $(
@@ -433,6 +460,37 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            fn build_duplex_stream_raw<D, E>(
+                &self,
+                config: &crate::DuplexStreamConfig,
+                sample_format: crate::SampleFormat,
+                data_callback: D,
+                error_callback: E,
+                timeout: Option<std::time::Duration>,
+            ) -> Result<Self::Stream, crate::BuildStreamError>
+            where
+                D: FnMut(&crate::Data, &mut crate::Data, &crate::DuplexCallbackInfo)
+                    + Send
+                    + 'static,
+                E: FnMut(crate::StreamError) + Send + 'static,
+            {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_duplex_stream_raw(
+                                config,
+                                sample_format,
+                                data_callback,
+                                error_callback,
+                                timeout,
+                            )
+                            .map(StreamInner::$HostVariant)
+                            .map(Stream::from),
+                    )*
+                }
+            }
         }
 
         impl crate::traits::HostTrait for Host {
@@ -458,6 +516,21 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            fn device_by_id(
+                &self,
+                id: &crate::DeviceId,
+            ) -> Result<Option<Self::Device>, crate::DevicesError> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => Ok(h
+                            .device_by_id(id)?
+                            .map(DeviceInner::$HostVariant)
+                            .map(Device::from)),
+                    )*
+                }
+            }
+
             fn default_input_device(&self) -> Option<Self::Device> {
                 match self.0 {
                     $(
@@ -479,6 +552,21 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            fn watch_devices<F>(
+                &self,
+                callback: F,
+            ) -> Result<crate::DeviceWatcher, crate::WatchDevicesError>
+            where
+                F: FnMut(crate::DeviceEvent) + Send + 'static,
+            {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => h.watch_devices(callback),
+                    )*
+                }
+            }
         }
 
         impl crate::traits::StreamTrait for Stream {
@@ -503,6 +591,42 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            fn can_pause(&self) -> bool {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => s.can_pause(),
+                    )*
+                }
+            }
+
+            fn stats(&self) -> Option<crate::StreamStats> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => s.stats(),
+                    )*
+                }
+            }
+
+            fn latency(&self) -> Option<std::time::Duration> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => s.latency(),
+                    )*
+                }
+            }
+
+            fn negotiated_config(&self) -> Option<crate::NegotiatedStreamConfig> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => s.negotiated_config(),
+                    )*
+                }
+            }
         }
 
         impl From<DeviceInner> for Device {
@@ -602,9 +726,15 @@ macro_rules! impl_platform_host {
 ))]
 mod platform_impl {
     pub use crate::host::alsa::{
-        Device as AlsaDevice, Devices as AlsaDevices, Host as AlsaHost, Stream as AlsaStream,
+        BufferLayout as AlsaBufferLayout, BufferParams as AlsaBufferParams,
+        CardAccess as AlsaCardAccess, Device as AlsaDevice, Devices as AlsaDevices,
+        Host as AlsaHost, HostOptions as AlsaHostOptions, Mixer as AlsaMixer,
+        MixerControl as AlsaMixerControl, MixerDirection as AlsaMixerDirection,
+        MixerWatcher as AlsaMixerWatcher, PassthroughConfig as AlsaPassthroughConfig,
+        PassthroughOutput as AlsaPassthroughOutput, PcmDirection as AlsaPcmDirection,
+        SharedStream as AlsaSharedStream, Sharing as AlsaSharing, Stream as AlsaStream,
         SupportedInputConfigs as AlsaSupportedInputConfigs,
-        SupportedOutputConfigs as AlsaSupportedOutputConfigs,
+        SupportedOutputConfigs as AlsaSupportedOutputConfigs, VolumeRange as AlsaVolumeRange,
     };
     #[cfg(feature = "jack")]
     pub use crate::host::jack::{
@@ -756,6 +886,7 @@ mod platform_impl {
 // TODO: Remove these in favour of using negative trait bounds if they stabilise.
 
 // A marker used to remove the `Send` and `Sync` traits.
+#[allow(dead_code)]
 struct NotSendSyncAcrossAllPlatforms(std::marker::PhantomData<*mut ()>);
 
 impl Default for NotSendSyncAcrossAllPlatforms {
@@ -1,7 +1,9 @@
-use std::{fmt::Display, mem};
+use std::{cmp::Ordering, fmt::Display, mem};
 #[cfg(target_os = "emscripten")]
 use wasm_bindgen::prelude::*;
 
+use dasp_sample::ToSample;
+
 pub use dasp_sample::{FromSample, Sample, I24, I48, U24, U48};
 
 /// Format that each sample has. Usually, this corresponds to the sampling
@@ -17,6 +19,9 @@ pub use dasp_sample::{FromSample, Sample, I24, I48, U24, U48};
 /// A good rule of thumb is to use [`SampleFormat::I16`] as this covers typical
 /// music (WAV, MP3) as well as typical audio input devices on most platforms,
 ///
+/// Samples are always delivered in the platform's native byte order. Hosts that support devices
+/// using the opposite byte order (e.g. `S16_BE` on ALSA) convert to and from the native order.
+///
 /// [`is_float`]: SampleFormat::is_float
 /// [`supported_input_configs`]: crate::Device::supported_input_configs
 #[cfg_attr(target_os = "emscripten", wasm_bindgen)]
@@ -32,11 +37,19 @@ pub enum SampleFormat {
     /// `I24` with a valid range of '-(1 << 23)..(1 << 23)' with `0` being the origin
     I24,
 
+    /// Like [`SampleFormat::I24`], but packed into 3 bytes per sample instead of being widened to
+    /// 4 bytes (e.g. ALSA's `S24_3LE` on little-endian platforms).
+    ///
+    /// There is no [`SizedSample`] type for this format. Use `build_*_stream_raw` and the
+    /// [`Data::as_packed_i24`](crate::Data::as_packed_i24) family of accessors instead.
+    I24Packed,
+
     /// `i32` with a valid range of `i32::MIN..=i32::MAX` with `0` being the origin.
     I32,
 
-    // /// `I48` with a valid range of '-(1 << 47)..(1 << 47)' with `0` being the origin
-    // I48,
+    /// `I48` with a valid range of '-(1 << 47)..(1 << 47)' with `0` being the origin
+    I48,
+
     /// `i64` with a valid range of `i64::MIN..=i64::MAX` with `0` being the origin.
     I64,
 
@@ -47,13 +60,13 @@ pub enum SampleFormat {
     U16,
 
     /// `U24` with a valid range of '0..16777216' with `1 << 23 == 8388608` being the origin
-    // U24,
+    U24,
 
     /// `u32` with a valid range of `u32::MIN..=u32::MAX` with `1 << 31` being the origin.
     U32,
 
     /// `U48` with a valid range of '0..(1 << 48)' with `1 << 47` being the origin
-    // U48,
+    U48,
 
     /// `u64` with a valid range of `u64::MIN..=u64::MAX` with `1 << 63` being the origin.
     U64,
@@ -63,27 +76,32 @@ pub enum SampleFormat {
 
     /// `f64` with a valid range of `-1.0..1.0` with `0.0` being the origin.
     F64,
+
+    /// G.711 μ-law companded [`MuLaw`] samples with `0xff` being the origin.
+    MuLaw,
+
+    /// G.711 A-law companded [`ALaw`] samples with `0xd5` being the origin.
+    ALaw,
 }
 
 impl SampleFormat {
     /// Returns the size in bytes of a sample of this format. This corresponds to
     /// the internal size of the rust primitives that are used to represent this
-    /// sample format (e.g., i24 has size of i32).
+    /// sample format (e.g., i24 has size of i32 and i48 has size of i64).
     #[inline]
     #[must_use]
     pub fn sample_size(&self) -> usize {
         match *self {
             SampleFormat::I8 | SampleFormat::U8 => mem::size_of::<i8>(),
             SampleFormat::I16 | SampleFormat::U16 => mem::size_of::<i16>(),
-            SampleFormat::I24 => mem::size_of::<i32>(),
-            // SampleFormat::U24 => mem::size_of::<i32>(),
+            SampleFormat::I24 | SampleFormat::U24 => mem::size_of::<i32>(),
+            SampleFormat::I24Packed => 3,
             SampleFormat::I32 | SampleFormat::U32 => mem::size_of::<i32>(),
-
-            // SampleFormat::I48 => mem::size_of::<i64>(),
-            // SampleFormat::U48 => mem::size_of::<i64>(),
+            SampleFormat::I48 | SampleFormat::U48 => mem::size_of::<i64>(),
             SampleFormat::I64 | SampleFormat::U64 => mem::size_of::<i64>(),
             SampleFormat::F32 => mem::size_of::<f32>(),
             SampleFormat::F64 => mem::size_of::<f64>(),
+            SampleFormat::MuLaw | SampleFormat::ALaw => mem::size_of::<u8>(),
         }
     }
 
@@ -95,8 +113,9 @@ impl SampleFormat {
             SampleFormat::I8
                 | SampleFormat::I16
                 | SampleFormat::I24
+                | SampleFormat::I24Packed
                 | SampleFormat::I32
-                // | SampleFormat::I48
+                | SampleFormat::I48
                 | SampleFormat::I64
         )
     }
@@ -108,9 +127,9 @@ impl SampleFormat {
             *self,
             SampleFormat::U8
                 | SampleFormat::U16
-                // | SampleFormat::U24
+                | SampleFormat::U24
                 | SampleFormat::U32
-                // | SampleFormat::U48
+                | SampleFormat::U48
                 | SampleFormat::U64
         )
     }
@@ -120,6 +139,14 @@ impl SampleFormat {
     pub fn is_float(&self) -> bool {
         matches!(*self, SampleFormat::F32 | SampleFormat::F64)
     }
+
+    /// Whether the samples are logarithmically companded, which is the case for
+    /// [`SampleFormat::MuLaw`] and [`SampleFormat::ALaw`].
+    #[inline]
+    #[must_use]
+    pub fn is_companded(&self) -> bool {
+        matches!(*self, SampleFormat::MuLaw | SampleFormat::ALaw)
+    }
 }
 
 impl Display for SampleFormat {
@@ -128,22 +155,205 @@ impl Display for SampleFormat {
             SampleFormat::I8 => "i8",
             SampleFormat::I16 => "i16",
             SampleFormat::I24 => "i24",
+            SampleFormat::I24Packed => "i24 (packed)",
             SampleFormat::I32 => "i32",
-            // SampleFormat::I48 => "i48",
+            SampleFormat::I48 => "i48",
             SampleFormat::I64 => "i64",
             SampleFormat::U8 => "u8",
             SampleFormat::U16 => "u16",
-            // SampleFormat::U24 => "u24",
+            SampleFormat::U24 => "u24",
             SampleFormat::U32 => "u32",
-            // SampleFormat::U48 => "u48",
+            SampleFormat::U48 => "u48",
             SampleFormat::U64 => "u64",
             SampleFormat::F32 => "f32",
             SampleFormat::F64 => "f64",
+            SampleFormat::MuLaw => "mu-law",
+            SampleFormat::ALaw => "a-law",
         }
         .fmt(f)
     }
 }
 
+/// Widen a packed 24-bit sample in native byte order to an [`I24`].
+pub fn unpack_i24(bytes: [u8; 3]) -> I24 {
+    let [a, b, c] = bytes;
+    let value = if cfg!(target_endian = "little") {
+        i32::from_le_bytes([a, b, c, 0])
+    } else {
+        i32::from_be_bytes([0, a, b, c])
+    };
+    // Shift the sign bit into place and back to sign-extend the value.
+    I24::new_unchecked((value << 8) >> 8)
+}
+
+/// Pack an [`I24`] into three bytes in native byte order.
+pub fn pack_i24(sample: I24) -> [u8; 3] {
+    let value = sample.inner();
+    if cfg!(target_endian = "little") {
+        let [a, b, c, _] = value.to_le_bytes();
+        [a, b, c]
+    } else {
+        let [_, a, b, c] = value.to_be_bytes();
+        [a, b, c]
+    }
+}
+
+/// A G.711 μ-law sample, which encodes a 14-bit linear sample in 8 bits.
+///
+/// Convert from and to linear samples with [`MuLaw::encode`] and [`MuLaw::decode`], or through
+/// the [`Sample`] trait.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct MuLaw(u8);
+
+impl MuLaw {
+    /// Create a sample from its encoded byte.
+    #[inline]
+    pub const fn from_bits(bits: u8) -> Self {
+        MuLaw(bits)
+    }
+
+    /// The encoded byte of this sample.
+    #[inline]
+    pub const fn to_bits(self) -> u8 {
+        self.0
+    }
+
+    /// Encode a linear sample.
+    pub fn encode<S: ToSample<i16>>(sample: S) -> Self {
+        const BIAS: u32 = 0x84;
+        const CLIP: u32 = 32635;
+
+        let sample: i16 = sample.to_sample_();
+        let negative = sample < 0;
+        let magnitude = (sample as i32).unsigned_abs().min(CLIP) + BIAS;
+        // The bias guarantees that the highest set bit is at least bit 7.
+        let exponent = 31 - magnitude.leading_zeros() - 7;
+        let mantissa = (magnitude >> (exponent + 3)) & 0x0f;
+        let bits = ((negative as u32) << 7) | (exponent << 4) | mantissa;
+        MuLaw(!(bits as u8))
+    }
+
+    /// Decode the sample to a linear sample.
+    pub fn decode<S: FromSample<i16>>(self) -> S {
+        const BIAS: i16 = 0x84;
+
+        let bits = !self.0;
+        let exponent = (bits >> 4) & 0x07;
+        let mantissa = (bits & 0x0f) as i16;
+        let magnitude = (((mantissa << 3) + BIAS) << exponent) - BIAS;
+        let sample = if bits & 0x80 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        };
+        S::from_sample_(sample)
+    }
+}
+
+/// A G.711 A-law sample, which encodes a 13-bit linear sample in 8 bits.
+///
+/// Convert from and to linear samples with [`ALaw::encode`] and [`ALaw::decode`], or through the
+/// [`Sample`] trait.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct ALaw(u8);
+
+impl ALaw {
+    /// Create a sample from its encoded byte.
+    #[inline]
+    pub const fn from_bits(bits: u8) -> Self {
+        ALaw(bits)
+    }
+
+    /// The encoded byte of this sample.
+    #[inline]
+    pub const fn to_bits(self) -> u8 {
+        self.0
+    }
+
+    /// Encode a linear sample.
+    pub fn encode<S: ToSample<i16>>(sample: S) -> Self {
+        let sample: i16 = sample.to_sample_();
+        let sample = sample >> 3;
+        let (mask, magnitude) = if sample >= 0 {
+            (0xd5, sample as u32)
+        } else {
+            (0x55, (-(sample as i32) - 1) as u32)
+        };
+        // Segment 0 covers 5 bits, and every following segment doubles the range.
+        let segment = (32 - magnitude.leading_zeros()).max(5) - 5;
+        let bits = match segment {
+            0 => (magnitude >> 1) & 0x0f,
+            1..=7 => (segment << 4) | ((magnitude >> segment) & 0x0f),
+            _ => 0x7f,
+        };
+        ALaw(bits as u8 ^ mask)
+    }
+
+    /// Decode the sample to a linear sample.
+    pub fn decode<S: FromSample<i16>>(self) -> S {
+        let bits = self.0 ^ 0x55;
+        let mantissa = ((bits & 0x0f) as i16) << 4;
+        let magnitude = match (bits >> 4) & 0x07 {
+            0 => mantissa + 0x08,
+            segment => (mantissa + 0x108) << (segment - 1),
+        };
+        let sample = if bits & 0x80 != 0 {
+            magnitude
+        } else {
+            -magnitude
+        };
+        S::from_sample_(sample)
+    }
+}
+
+macro_rules! impl_companded_sample {
+    ($($T:ident => $equilibrium:expr),*) => {$(
+        impl PartialOrd for $T {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                self.decode::<i16>().partial_cmp(&other.decode::<i16>())
+            }
+        }
+
+        impl Sample for $T {
+            type Signed = i16;
+            type Float = f32;
+            const EQUILIBRIUM: Self = $T($equilibrium);
+        }
+
+        impl FromSample<i16> for $T {
+            #[inline]
+            fn from_sample_(s: i16) -> Self {
+                $T::encode(s)
+            }
+        }
+
+        impl FromSample<f32> for $T {
+            #[inline]
+            fn from_sample_(s: f32) -> Self {
+                $T::encode(s)
+            }
+        }
+
+        impl FromSample<$T> for i16 {
+            #[inline]
+            fn from_sample_(s: $T) -> Self {
+                s.decode()
+            }
+        }
+
+        impl FromSample<$T> for f32 {
+            #[inline]
+            fn from_sample_(s: $T) -> Self {
+                s.decode()
+            }
+        }
+    )*};
+}
+
+impl_companded_sample!(MuLaw => 0xff, ALaw => 0xd5);
+
 pub trait SizedSample: Sample {
     const FORMAT: SampleFormat;
 }
@@ -164,9 +374,9 @@ impl SizedSample for i32 {
     const FORMAT: SampleFormat = SampleFormat::I32;
 }
 
-// impl SizedSample for I48 {
-//     const FORMAT: SampleFormat = SampleFormat::I48;
-// }
+impl SizedSample for I48 {
+    const FORMAT: SampleFormat = SampleFormat::I48;
+}
 
 impl SizedSample for i64 {
     const FORMAT: SampleFormat = SampleFormat::I64;
@@ -180,17 +390,17 @@ impl SizedSample for u16 {
     const FORMAT: SampleFormat = SampleFormat::U16;
 }
 
-// impl SizedSample for U24 {
-//     const FORMAT: SampleFormat = SampleFormat::U24;
-// }
+impl SizedSample for U24 {
+    const FORMAT: SampleFormat = SampleFormat::U24;
+}
 
 impl SizedSample for u32 {
     const FORMAT: SampleFormat = SampleFormat::U32;
 }
 
-// impl SizedSample for U48 {
-//     const FORMAT: SampleFormat = SampleFormat::U48;
-// }
+impl SizedSample for U48 {
+    const FORMAT: SampleFormat = SampleFormat::U48;
+}
 
 impl SizedSample for u64 {
     const FORMAT: SampleFormat = SampleFormat::U64;
@@ -203,3 +413,68 @@ impl SizedSample for f32 {
 impl SizedSample for f64 {
     const FORMAT: SampleFormat = SampleFormat::F64;
 }
+
+impl SizedSample for MuLaw {
+    const FORMAT: SampleFormat = SampleFormat::MuLaw;
+}
+
+impl SizedSample for ALaw {
+    const FORMAT: SampleFormat = SampleFormat::ALaw;
+}
+
+#[test]
+fn test_sample_size_matches_sized_sample() {
+    fn size_of_format<T: SizedSample>() -> (SampleFormat, usize) {
+        (T::FORMAT, mem::size_of::<T>())
+    }
+
+    for (format, size) in [
+        size_of_format::<i8>(),
+        size_of_format::<i16>(),
+        size_of_format::<I24>(),
+        size_of_format::<i32>(),
+        size_of_format::<I48>(),
+        size_of_format::<i64>(),
+        size_of_format::<u8>(),
+        size_of_format::<u16>(),
+        size_of_format::<U24>(),
+        size_of_format::<u32>(),
+        size_of_format::<U48>(),
+        size_of_format::<u64>(),
+        size_of_format::<f32>(),
+        size_of_format::<f64>(),
+        size_of_format::<MuLaw>(),
+        size_of_format::<ALaw>(),
+    ] {
+        assert_eq!(format.sample_size(), size, "{format}");
+        assert_eq!(
+            format.is_int() as u8
+                + format.is_uint() as u8
+                + format.is_float() as u8
+                + format.is_companded() as u8,
+            1,
+            "{format}"
+        );
+    }
+}
+
+#[test]
+fn test_companded_round_trip() {
+    for bits in 0..=u8::MAX {
+        // μ-law has a negative zero, which encodes as positive zero.
+        if bits != 0x7f {
+            let sample = MuLaw::from_bits(bits);
+            assert_eq!(MuLaw::encode(sample.decode::<i16>()), sample);
+        }
+        let sample = ALaw::from_bits(bits);
+        assert_eq!(ALaw::encode(sample.decode::<i16>()), sample);
+    }
+
+    assert_eq!(MuLaw::encode(0i16), MuLaw::EQUILIBRIUM);
+    assert_eq!(ALaw::encode(0i16), ALaw::EQUILIBRIUM);
+    assert_eq!(MuLaw::from_bits(0x80).decode::<i16>(), 32124);
+    assert_eq!(MuLaw::from_bits(0x00).decode::<i16>(), -32124);
+    assert_eq!(ALaw::from_bits(0xaa).decode::<i16>(), 32256);
+    assert_eq!(ALaw::from_bits(0x2a).decode::<i16>(), -32256);
+    assert!(MuLaw::encode(-1.0f32) < MuLaw::encode(0.5f32));
+}
@@ -3,10 +3,12 @@
 use std::time::Duration;
 
 use crate::{
-    BuildStreamError, Data, DefaultStreamConfigError, DeviceNameError, DevicesError,
-    InputCallbackInfo, InputDevices, OutputCallbackInfo, OutputDevices, PauseStreamError,
-    PlayStreamError, SampleFormat, SizedSample, StreamConfig, StreamError, SupportedStreamConfig,
-    SupportedStreamConfigRange, SupportedStreamConfigsError,
+    BuildStreamError, Data, DefaultStreamConfigError, DeviceEvent, DeviceId, DeviceNameError,
+    DeviceWatcher, DevicesError, DuplexCallbackInfo, DuplexStreamConfig, InputCallbackInfo,
+    InputDevices, NegotiatedStreamConfig, OutputCallbackInfo, OutputDevices, PauseStreamError,
+    PlayStreamError, SampleFormat, SizedSample, StreamConfig, StreamError, StreamStats,
+    SupportedStreamConfig, SupportedStreamConfigRange, SupportedStreamConfigSet,
+    SupportedStreamConfigsError, WatchDevicesError,
 };
 
 /// A [`Host`] provides access to the available audio devices on the system.
@@ -44,6 +46,15 @@ pub trait HostTrait {
     /// Can be empty if the system does not support audio in general.
     fn devices(&self) -> Result<Self::Devices, DevicesError>;
 
+    /// The device with the given [`id`](DeviceTrait::id).
+    ///
+    /// Returns `None` if no such device is currently available.
+    fn device_by_id(&self, id: &DeviceId) -> Result<Option<Self::Device>, DevicesError> {
+        Ok(self
+            .devices()?
+            .find(|device| device.id().as_ref() == Ok(id)))
+    }
+
     /// The default input audio device on the system.
     ///
     /// Returns `None` if no input device is available.
@@ -69,6 +80,20 @@ pub trait HostTrait {
     fn output_devices(&self) -> Result<OutputDevices<Self::Devices>, DevicesError> {
         Ok(self.devices()?.filter(DeviceTrait::supports_output))
     }
+
+    /// Call `callback` whenever a device is added, removed or changed, until the returned
+    /// [`DeviceWatcher`] is dropped.
+    ///
+    /// The callback is called on a separate thread. Devices that are present when this is called
+    /// are not reported.
+    ///
+    /// Returns [`WatchDevicesError::NotSupported`] if the host can't notify of device changes.
+    fn watch_devices<F>(&self, _callback: F) -> Result<DeviceWatcher, WatchDevicesError>
+    where
+        F: FnMut(DeviceEvent) + Send + 'static,
+    {
+        Err(WatchDevicesError::NotSupported)
+    }
 }
 
 /// A device that is capable of audio input and/or output.
@@ -89,6 +114,14 @@ pub trait DeviceTrait {
     /// The human-readable name of the device.
     fn name(&self) -> Result<String, DeviceNameError>;
 
+    /// A stable identifier of the device, which can be saved to find the same device again with
+    /// [`HostTrait::device_by_id`].
+    ///
+    /// Hosts without a more stable identifier use the name of the device.
+    fn id(&self) -> Result<DeviceId, DeviceNameError> {
+        self.name().map(DeviceId::from)
+    }
+
     /// True if the device supports audio input, otherwise false
     fn supports_input(&self) -> bool {
         self.supported_input_configs()
@@ -117,6 +150,30 @@ pub trait DeviceTrait {
         &self,
     ) -> Result<Self::SupportedOutputConfigs, SupportedStreamConfigsError>;
 
+    /// The supported input stream formats, grouped into as few [`SupportedStreamConfigSet`]s as
+    /// possible.
+    ///
+    /// This describes the same configs as [`supported_input_configs`](Self::supported_input_configs),
+    /// but in far fewer entries for devices that support many channel counts or only discrete
+    /// sample rates.
+    fn supported_input_config_sets(
+        &self,
+    ) -> Result<Vec<SupportedStreamConfigSet>, SupportedStreamConfigsError> {
+        self.supported_input_configs()
+            .map(SupportedStreamConfigSet::from_ranges)
+    }
+
+    /// The supported output stream formats, grouped into as few [`SupportedStreamConfigSet`]s as
+    /// possible.
+    ///
+    /// See [`supported_input_config_sets`](Self::supported_input_config_sets).
+    fn supported_output_config_sets(
+        &self,
+    ) -> Result<Vec<SupportedStreamConfigSet>, SupportedStreamConfigsError> {
+        self.supported_output_configs()
+            .map(SupportedStreamConfigSet::from_ranges)
+    }
+
     /// The default input stream format for the device.
     fn default_input_config(&self) -> Result<SupportedStreamConfig, DefaultStreamConfigError>;
 
@@ -204,6 +261,61 @@ pub trait DeviceTrait {
     where
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(StreamError) + Send + 'static;
+
+    /// Create a duplex stream, which captures from and plays to this device in lockstep.
+    ///
+    /// Each call to the data callback receives the captured input and fills the output, which hold
+    /// the same number of frames, along with a timestamp shared by both. Unlike an input stream
+    /// bridged to an output stream through a ring buffer, this adds no latency of its own.
+    ///
+    /// Fails with [`BuildStreamError::StreamConfigNotSupported`] if the host doesn't support
+    /// duplex streams.
+    fn build_duplex_stream<T, D, E>(
+        &self,
+        config: &DuplexStreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        T: SizedSample,
+        D: FnMut(&[T], &mut [T], &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        self.build_duplex_stream_raw(
+            config,
+            T::FORMAT,
+            move |input, output, info| {
+                data_callback(
+                    input
+                        .as_slice()
+                        .expect("host supplied incorrect sample type"),
+                    output
+                        .as_slice_mut()
+                        .expect("host supplied incorrect sample type"),
+                    info,
+                )
+            },
+            error_callback,
+            timeout,
+        )
+    }
+
+    /// Create a dynamically typed duplex stream.
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        _config: &DuplexStreamConfig,
+        _sample_format: SampleFormat,
+        _data_callback: D,
+        _error_callback: E,
+        _timeout: Option<Duration>,
+    ) -> Result<Self::Stream, BuildStreamError>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(StreamError) + Send + 'static,
+    {
+        Err(BuildStreamError::StreamConfigNotSupported)
+    }
 }
 
 /// A stream created from [`Device`](DeviceTrait), with methods to control playback.
@@ -220,4 +332,45 @@ pub trait StreamTrait {
     /// Note: Not all devices support suspending the stream at the hardware level. This method may
     /// fail in these cases.
     fn pause(&self) -> Result<(), PauseStreamError>;
+
+    /// Whether [`pause`](Self::pause) suspends the stream in place, so that [`play`](Self::play)
+    /// resumes it where it stopped.
+    ///
+    /// If this is `false`, the stream may still be paused, but by stopping it altogether. Any
+    /// audio that was buffered at that point is discarded, and the stream starts over when played
+    /// again. Hosts that don't tell how they pause return `false`.
+    fn can_pause(&self) -> bool {
+        false
+    }
+
+    /// A snapshot of the statistics of the stream so far, such as the number of frames processed
+    /// and the time spent in the data callback.
+    ///
+    /// Returns `None` if the host doesn't collect statistics.
+    fn stats(&self) -> Option<StreamStats> {
+        None
+    }
+
+    /// The current latency of the stream.
+    ///
+    /// For output streams, this is the time from the data callback writing a frame until the
+    /// device plays it. For input streams, it is the time from the device capturing a frame until
+    /// the data callback receives it. For duplex streams, it is the round trip from capture to
+    /// playback. It is made up of the delay of the device and of the audio
+    /// buffered for it, plus the duration of the buffer passed to the data callback, and may change
+    /// while the stream runs.
+    ///
+    /// Returns `None` if the host doesn't report the latency, or can't measure it at the moment.
+    fn latency(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The configuration the stream actually runs with, which may differ from the requested one,
+    /// for instance if it asked for [`BufferSize::Default`](crate::BufferSize::Default). It is
+    /// known as soon as the stream is built, before the first call to the data callback.
+    ///
+    /// Returns `None` if the host doesn't report the negotiated configuration.
+    fn negotiated_config(&self) -> Option<NegotiatedStreamConfig> {
+        None
+    }
 }
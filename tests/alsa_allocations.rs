@@ -0,0 +1,86 @@
+//! Checks that ALSA output streams don't allocate on the audio thread once they run.
+//!
+//! This is a test binary of its own, as counting allocations replaces the global allocator.
+
+#![cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd"
+))]
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{BufferSize, Data, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::mpsc;
+use std::time::Duration;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+// The number of heap allocations made by the current thread so far.
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn output_conversion_does_not_allocate() {
+    // The "null" PCM accepts any format, so the `I24` stream is converted to `S24_LE` or
+    // `S24_3LE` by the codec.
+    let host = cpal::host_from_id(cpal::HostId::Alsa).unwrap();
+    let device = host
+        .devices()
+        .unwrap()
+        .find(|device| device.name().unwrap() == "null")
+        .unwrap();
+    let config = StreamConfig {
+        channels: 2,
+        sample_rate: SampleRate(48_000),
+        buffer_size: BufferSize::Fixed(256),
+    };
+    let (tx, rx) = mpsc::channel();
+    let mut callbacks = 0;
+    let mut allocated = 0;
+    let stream = device
+        .build_output_stream_raw(
+            &config,
+            SampleFormat::I24,
+            move |data: &mut Data, _: &OutputCallbackInfo| {
+                data.bytes_mut().fill(0);
+                callbacks += 1;
+                if callbacks == 10 {
+                    allocated = allocations();
+                } else if callbacks == 100 {
+                    let _ = tx.send(allocations() - allocated);
+                }
+            },
+            |err| panic!("{}", err),
+            None,
+        )
+        .unwrap();
+    stream.play().unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(0));
+}